@@ -0,0 +1,47 @@
+//! Rendering a bar's worth of labels with draw_texts vs looped calls to draw_text
+use criterion::{criterion_group, criterion_main, Criterion};
+use penrose::{x::XConn, x11rb::RustConn};
+use x11_draw::{Draw, Rect};
+
+const W: u32 = 1920;
+const H: u32 = 20;
+const N_LABELS: u32 = 20;
+const FONT: &str = "ProFont For Powerline:size=10";
+
+fn labels() -> Vec<(String, Rect, u32, bool)> {
+    let w = W / N_LABELS;
+
+    (0..N_LABELS)
+        .map(|i| {
+            let r = Rect {
+                x: (i * w) as i32,
+                y: 0,
+                w,
+                h: H,
+            };
+
+            (format!("label {i}"), r, 4, i % 2 == 0)
+        })
+        .collect()
+}
+
+fn bench_draw_texts(c: &mut Criterion) {
+    let conn = RustConn::new().unwrap();
+    let mut drw = Draw::new(*conn.root(), W, H, FONT).unwrap();
+    drw.add_colorscheme("default", "#f2e5bc", "#282828")
+        .unwrap();
+    let items = labels();
+
+    c.bench_function("draw_text looped", |b| {
+        b.iter(|| {
+            for (txt, r, lpad, invert) in items.iter() {
+                drw.draw_text(txt, *lpad, *r, *invert).unwrap();
+            }
+        })
+    });
+
+    c.bench_function("draw_texts", |b| b.iter(|| drw.draw_texts(&items).unwrap()));
+}
+
+criterion_group!(benches, bench_draw_texts);
+criterion_main!(benches);
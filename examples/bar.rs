@@ -0,0 +1,75 @@
+//! A minimal status bar built from text modules using [Bar]
+use penrose::{
+    pure::geometry::Rect as PRect,
+    x::{Atom, WinType, XConn},
+    x11rb::RustConn,
+};
+use std::time::{Duration, SystemTime};
+use x11_draw::{Bar, Draw, Error, Module, Rect};
+
+type Result<T> = std::result::Result<T, Error>;
+
+const W: u32 = 800;
+const H: u32 = 24;
+const PAD: u32 = 6;
+const FONT: &str = "ProFont For Powerline:size=12";
+
+struct Text(String);
+
+impl Module for Text {
+    fn width(&self, draw: &mut Draw) -> Result<u32> {
+        let (w, _) = draw.text_extent(&self.0)?;
+        Ok(w as u32 + 2 * PAD)
+    }
+
+    fn render(&self, draw: &mut Draw, r: Rect) -> Result<()> {
+        draw.draw_text(&self.0, PAD, r, false)
+    }
+}
+
+struct Uptime(SystemTime);
+
+impl Uptime {
+    fn label(&self) -> String {
+        let secs = self.0.elapsed().unwrap_or_default().as_secs();
+        format!("up {}m {:02}s", secs / 60, secs % 60)
+    }
+}
+
+impl Module for Uptime {
+    fn width(&self, draw: &mut Draw) -> Result<u32> {
+        Text(self.label()).width(draw)
+    }
+
+    fn render(&self, draw: &mut Draw, r: Rect) -> Result<()> {
+        Text(self.label()).render(draw, r)
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let conn = RustConn::new()?;
+    let screen_rects = conn.screen_details()?;
+    let PRect { x, y, .. } = screen_rects.last().unwrap();
+
+    let w = conn.create_window(
+        WinType::InputOutput(Atom::NetWindowTypeDock),
+        PRect::new(*x, *y, W, H),
+        false,
+    )?;
+
+    let mut drw = Draw::new(*conn.root(), W, H, FONT)?;
+    drw.add_colorscheme("primary", "#f2e5bc", "#282828")?;
+
+    let mut bar = Bar::new(drw, *w);
+    bar.push_left(Text("[1] 2 3".to_string()))
+        .push_center(Text("x11-draw bar example".to_string()))
+        .push_right(Uptime(SystemTime::now()));
+
+    conn.map(w)?;
+    for _ in 0..30 {
+        bar.render()?;
+        std::thread::sleep(Duration::from_secs(1));
+    }
+
+    Ok(())
+}
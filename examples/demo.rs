@@ -27,19 +27,14 @@ fn main() -> anyhow::Result<()> {
     drw.add_colorscheme("primary", "#f2e5bc", "#282828")?;
     drw.add_colorscheme("secondary", "#458588", "#b16286")?;
 
-    let r = Rect {
-        x: 0,
-        y: 0,
-        w: W,
-        h: H,
-    };
+    let r = Rect::from_size(W, H);
 
     for n in 0..4 {
         let scheme = if n % 2 == 0 { "primary" } else { "secondary" };
         let invert = n >= 2;
         drw.set_colorscheme(scheme)?;
         drw.fill_rect(r, invert)?;
-        drw.flush_to(*w, r);
+        drw.flush_to(*w, r)?;
         conn.map(w)?;
 
         std::thread::sleep(std::time::Duration::from_secs(1));
@@ -28,12 +28,7 @@ fn main() -> anyhow::Result<()> {
     drw.add_colorscheme("primary", "#f2e5bc", "#282828")?;
     drw.add_colorscheme("secondary", "#458588", "#b16286")?;
 
-    let r = Rect {
-        x: 0,
-        y: 0,
-        w: W,
-        h: H,
-    };
+    let r = Rect::from_size(W, H);
     let r_txt = Rect {
         x: 10,
         y: 10,
@@ -50,13 +45,12 @@ fn main() -> anyhow::Result<()> {
         let scheme = if n % 2 == 0 { "primary" } else { "secondary" };
         let invert = n >= 2;
 
-        drw.set_colorscheme("border")?;
-        drw.fill_rect(r, invert)?;
-
-        drw.set_colorscheme(scheme)?;
-        drw.draw_text(txt, 4, r_txt, invert)?;
-
-        drw.flush_to(*w, r);
+        drw.frame()
+            .set_colorscheme("border")?
+            .fill_rect(r, invert)?
+            .set_colorscheme(scheme)?
+            .draw_text(txt, 4, r_txt, invert)?
+            .flush_to(*w, r)?;
         conn.map(w)?;
         std::thread::sleep(std::time::Duration::from_secs(1));
     }
@@ -0,0 +1,129 @@
+//! A status bar built from modules laid out on the left, center and right of a window
+//!
+//! Creating and mapping the window itself is left to the caller (for example using penrose)
+//! so that the bar can be docked however the window manager in use expects.
+use crate::{Draw, Rect, Result};
+
+/// A single element of a [Bar] such as a workspace list, window title or clock.
+pub trait Module {
+    /// The width in pixels that this module needs in order to render its current contents.
+    fn width(&self, draw: &mut Draw) -> Result<u32>;
+
+    /// Render the module into `r`, which has the width returned by [Module::width] and the
+    /// full height of the bar.
+    fn render(&self, draw: &mut Draw, r: Rect) -> Result<()>;
+}
+
+/// A [Draw] along with the window it is flushed to and the [Module]s that make up its
+/// contents.
+///
+/// Left modules are placed in order from the left edge and right modules in order so that
+/// the last ends at the right edge. Center modules are centered in the bar as a group,
+/// shifted right if needed to avoid overlapping the left modules.
+pub struct Bar {
+    draw: Draw,
+    win: u32,
+    left: Vec<Box<dyn Module>>,
+    center: Vec<Box<dyn Module>>,
+    right: Vec<Box<dyn Module>>,
+}
+
+impl std::fmt::Debug for Bar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Bar")
+            .field("draw", &self.draw)
+            .field("win", &self.win)
+            .field("left", &self.left.len())
+            .field("center", &self.center.len())
+            .field("right", &self.right.len())
+            .finish()
+    }
+}
+
+impl Bar {
+    /// Create a new bar with no modules that renders using `draw` and flushes to `win`.
+    ///
+    /// The window should be the same size as the drawable.
+    pub fn new(draw: Draw, win: u32) -> Self {
+        Self {
+            draw,
+            win,
+            left: Vec::new(),
+            center: Vec::new(),
+            right: Vec::new(),
+        }
+    }
+
+    /// Add a module to the left section, after any left modules that were already added.
+    pub fn push_left(&mut self, m: impl Module + 'static) -> &mut Self {
+        self.left.push(Box::new(m));
+        self
+    }
+
+    /// Add a module to the center section, after any center modules that were already added.
+    pub fn push_center(&mut self, m: impl Module + 'static) -> &mut Self {
+        self.center.push(Box::new(m));
+        self
+    }
+
+    /// Add a module to the right section, after any right modules that were already added.
+    pub fn push_right(&mut self, m: impl Module + 'static) -> &mut Self {
+        self.right.push(Box::new(m));
+        self
+    }
+
+    /// The [Draw] used for rendering, for registering colorschemes or drawing directly.
+    pub fn draw(&mut self) -> &mut Draw {
+        &mut self.draw
+    }
+
+    /// The window that the bar is flushed to.
+    pub fn win(&self) -> u32 {
+        self.win
+    }
+
+    /// Clear the bar, render every module in its position and then flush the result to the
+    /// window.
+    pub fn render(&mut self) -> Result<()> {
+        let (w, h) = (self.draw.w, self.draw.h);
+        self.draw.clear()?;
+
+        let left = widths(&self.left, &mut self.draw)?;
+        let center = widths(&self.center, &mut self.draw)?;
+        let right = widths(&self.right, &mut self.draw)?;
+        let total = |ws: &[u32]| ws.iter().map(|&w| w as i64).sum::<i64>();
+
+        let left_end = total(&left);
+        let center_start = ((w as i64 - total(&center)) / 2).max(left_end);
+        let right_start = w as i64 - total(&right);
+
+        for (ms, ws, x) in [
+            (&self.left, left, 0),
+            (&self.center, center, center_start),
+            (&self.right, right, right_start),
+        ] {
+            let mut x = x;
+            for (m, mw) in ms.iter().zip(ws) {
+                let r = Rect {
+                    x: x.clamp(i32::MIN as i64, i32::MAX as i64) as i32,
+                    y: 0,
+                    w: mw,
+                    h,
+                };
+                m.render(&mut self.draw, r)?;
+                x += mw as i64;
+            }
+        }
+
+        self.draw.flush_to(self.win, Rect::from_size(w, h))
+    }
+
+    /// Give back the underlying [Draw], dropping the modules.
+    pub fn into_draw(self) -> Draw {
+        self.draw
+    }
+}
+
+fn widths(modules: &[Box<dyn Module>], draw: &mut Draw) -> Result<Vec<u32>> {
+    modules.iter().map(|m| m.width(draw)).collect()
+}
@@ -0,0 +1,160 @@
+//! A stack of clip regions applied to everything drawn into the drawable
+//!
+//! Regions are purely client side in Xlib so they outlive the connection they were used on
+//! and only need to be applied again to the GC after a reconnect. Anything that uses the GC
+//! to draw somewhere other than the drawable (flushing or drawing into a scratch pixmap)
+//! needs to suspend the clip while doing so.
+use crate::{Draw, Rect};
+use x11::{
+    xft::{XftDraw, XftDrawSetClip},
+    xlib::{
+        Region, XCreateRegion, XDestroyRegion, XIntersectRegion, XSetClipMask, XSetRegion,
+        XSubtractRegion, XUnionRectWithRegion,
+    },
+    xrender::{Picture, XRenderSetPictureClipRegion},
+};
+
+impl Draw {
+    /// Confine all subsequent drawing to `r` with its corners rounded off to the given radius,
+    /// intersected with any clip that is already in place, until a matching call to
+    /// [Draw::pop_clip].
+    pub fn push_clip_rounded(&mut self, r: Rect, radius: u32) {
+        self.assert_thread();
+        let r = self.translate_rect(r);
+        let radius = radius.min(r.w / 2).min(r.h / 2);
+
+        unsafe {
+            let region = XCreateRegion();
+            for row in rounded_rows(r, radius) {
+                XUnionRectWithRegion(&mut row.as_xrectangle(), region, region);
+            }
+            self.push_clip_region(region);
+        }
+    }
+
+    /// Confine all subsequent drawing to the part of `outer` that is not inside `hole`,
+    /// intersected with any clip that is already in place, until a matching call to
+    /// [Draw::pop_clip].
+    pub fn push_clip_except(&mut self, outer: Rect, hole: Rect) {
+        self.assert_thread();
+        let (outer, hole) = (self.translate_rect(outer), self.translate_rect(hole));
+
+        unsafe {
+            let region = XCreateRegion();
+            let cutout = XCreateRegion();
+            XUnionRectWithRegion(&mut outer.as_xrectangle(), region, region);
+            XUnionRectWithRegion(&mut hole.as_xrectangle(), cutout, cutout);
+            XSubtractRegion(region, cutout, region);
+            XDestroyRegion(cutout);
+            self.push_clip_region(region);
+        }
+    }
+
+    /// Remove the most recently pushed clip (if there is one).
+    pub fn pop_clip(&mut self) {
+        self.assert_thread();
+        if let Some(region) = self.clips.pop() {
+            unsafe { XDestroyRegion(region) };
+            self.apply_clip();
+        }
+    }
+
+    // Takes ownership of region, narrowing it to the current clip
+    pub(crate) unsafe fn push_clip_region(&mut self, region: Region) {
+        if let Some(&top) = self.clips.last() {
+            XIntersectRegion(region, top, region);
+        }
+        self.clips.push(region);
+        self.apply_clip();
+    }
+
+    pub(crate) fn apply_clip(&self) {
+        unsafe {
+            match self.clips.last() {
+                Some(&region) => XSetRegion(self.dpy, self.gc, region),
+                None => XSetClipMask(self.dpy, self.gc, 0),
+            };
+        }
+    }
+
+    pub(crate) fn suspend_clip(&self) {
+        if !self.clips.is_empty() {
+            unsafe { XSetClipMask(self.dpy, self.gc, 0) };
+        }
+    }
+
+    pub(crate) fn resume_clip(&self) {
+        if !self.clips.is_empty() {
+            self.apply_clip();
+        }
+    }
+
+    // A new region covering the part of r (which has already had the current translation
+    // applied) that is inside the current clip
+    pub(crate) unsafe fn clip_region(&self, r: Rect) -> Region {
+        let region = XCreateRegion();
+        XUnionRectWithRegion(&mut r.as_xrectangle(), region, region);
+        if let Some(&top) = self.clips.last() {
+            XIntersectRegion(region, top, region);
+        }
+
+        region
+    }
+
+    pub(crate) unsafe fn clip_xft_draw(&self, d: *mut XftDraw) {
+        if let Some(&region) = self.clips.last() {
+            XftDrawSetClip(d, region);
+        }
+    }
+
+    pub(crate) unsafe fn clip_picture(&self, pic: Picture) {
+        if let Some(&region) = self.clips.last() {
+            XRenderSetPictureClipRegion(self.dpy, pic, region);
+        }
+    }
+
+    pub(crate) fn free_clips(&mut self) {
+        for region in self.clips.drain(..) {
+            unsafe { XDestroyRegion(region) };
+        }
+    }
+}
+
+// One rect per row for the rounded top and bottom edges and a single rect for the straight
+// section between them
+fn rounded_rows(r: Rect, radius: u32) -> Vec<Rect> {
+    if radius == 0 {
+        return vec![r];
+    }
+
+    let rad = radius as f64;
+    let mut rows = Vec::with_capacity(2 * radius as usize + 1);
+
+    for i in 0..radius {
+        // Distance from the center of the corner circle to the middle of this row
+        let dy = rad - i as f64 - 0.5;
+        let inset = (rad - (rad * rad - dy * dy).sqrt()).round() as u32;
+        let w = r.w.saturating_sub(2 * inset);
+        let x = r.x + inset as i32;
+        rows.push(Rect {
+            x,
+            y: r.y + i as i32,
+            w,
+            h: 1,
+        });
+        rows.push(Rect {
+            x,
+            y: r.y + (r.h - 1 - i) as i32,
+            w,
+            h: 1,
+        });
+    }
+
+    rows.push(Rect {
+        y: r.y + radius as i32,
+        h: r.h - 2 * radius,
+        ..r
+    });
+
+    rows
+}
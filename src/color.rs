@@ -0,0 +1,198 @@
+//! Owned color allocations made through Xft
+use crate::{visual::VisualInfo, Draw, Error, Result};
+use std::{ffi::CString, mem::MaybeUninit};
+use x11::{
+    xft::{XftColor, XftColorAllocName, XftColorAllocValue, XftColorFree},
+    xlib::Display,
+    xrender::XRenderColor,
+};
+
+/// A color allocated using Xft that is freed again when it is dropped.
+///
+/// Colors are deliberately not Clone as each one owns its allocation.
+#[derive(Debug)]
+pub(crate) struct Color {
+    dpy: *mut Display,
+    vis: VisualInfo,
+    xft: XftColor,
+}
+
+impl Color {
+    /// Allocate a color from any name or spec understood by Xlib (e.g. "#282828" or "red")
+    pub(crate) fn from_name(dpy: *mut Display, vis: VisualInfo, name: &str) -> Result<Self> {
+        let c_name = CString::new(name)?;
+        let mut xft = MaybeUninit::<XftColor>::uninit();
+
+        let res = unsafe {
+            XftColorAllocName(
+                dpy,
+                vis.visual,
+                vis.colormap,
+                c_name.as_ptr(),
+                xft.as_mut_ptr(),
+            )
+        };
+
+        log_debug!("XftColorAllocName('{name}') returned {res}");
+        if res == 0 {
+            return Err(Error::UnableToAllocateColor);
+        }
+
+        Ok(Self {
+            dpy,
+            vis,
+            // SAFETY: XftColorAllocName has initialised the color on success
+            xft: unsafe { xft.assume_init() },
+        })
+    }
+
+    /// Allocate a named color (as for [Color::from_name]) with an 8-bit alpha value
+    pub(crate) fn from_name_with_alpha(
+        dpy: *mut Display,
+        vis: VisualInfo,
+        name: &str,
+        alpha: u8,
+    ) -> Result<Self> {
+        // Xft has no way to parse a name without also allocating it
+        let opaque = Self::from_name(dpy, vis, name)?;
+        let color = XRenderColor {
+            alpha: channel16(alpha),
+            ..opaque.xft.color
+        };
+
+        Self::from_render_color(dpy, vis, &color)
+    }
+
+    pub(crate) fn from_rgba(
+        dpy: *mut Display,
+        vis: VisualInfo,
+        r: u8,
+        g: u8,
+        b: u8,
+        a: u8,
+    ) -> Result<Self> {
+        Self::from_render_color(dpy, vis, &xrender_color([r, g, b, a]))
+    }
+
+    fn from_render_color(dpy: *mut Display, vis: VisualInfo, color: &XRenderColor) -> Result<Self> {
+        let mut xft = MaybeUninit::<XftColor>::uninit();
+
+        let res =
+            unsafe { XftColorAllocValue(dpy, vis.visual, vis.colormap, color, xft.as_mut_ptr()) };
+
+        log_debug!("XftColorAllocValue({color:?}) returned {res}");
+        if res == 0 {
+            return Err(Error::UnableToAllocateColor);
+        }
+
+        Ok(Self {
+            dpy,
+            vis,
+            // SAFETY: XftColorAllocValue has initialised the color on success
+            xft: unsafe { xft.assume_init() },
+        })
+    }
+
+    /// Allocate a color from a `0xAARRGGBB` value
+    pub(crate) fn from_argb(dpy: *mut Display, vis: VisualInfo, argb: u32) -> Result<Self> {
+        let [a, r, g, b] = argb.to_be_bytes();
+
+        Self::from_rgba(dpy, vis, r, g, b, a)
+    }
+
+    /// Allocate the same color again on a different connection
+    pub(crate) fn realloc(&self, dpy: *mut Display, vis: VisualInfo) -> Result<Self> {
+        Self::from_render_color(dpy, vis, &self.xft.color)
+    }
+
+    pub(crate) fn pixel(&self) -> u64 {
+        self.xft.pixel
+    }
+
+    pub(crate) fn as_xft_color(&self) -> *const XftColor {
+        &self.xft
+    }
+}
+
+impl Drop for Color {
+    fn drop(&mut self) {
+        // SAFETY: the Display we have a pointer to is freed by the parent draw
+        unsafe {
+            XftColorFree(self.dpy, self.vis.visual, self.vis.colormap, &mut self.xft);
+        }
+    }
+}
+
+impl Draw {
+    /// Linearly interpolate between two `#RRGGBB` colors, returning the color `t` of the way
+    /// from `a` to `b` in the same format. `t` is clamped to 0.0..=1.0.
+    ///
+    /// The result can be passed directly to methods accepting colors such as
+    /// [Draw::fill_rect_color].
+    pub fn lerp_color(&self, a: &str, b: &str, t: f64) -> Result<String> {
+        let (a, b) = (parse_hex(a)?, parse_hex(b)?);
+        let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+        let lerp = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+        let [r, g, b] = [0, 1, 2].map(|i| lerp(a[i], b[i]));
+
+        Ok(format!("#{r:02x}{g:02x}{b:02x}"))
+    }
+
+    /// Pick whichever of black or white has the higher WCAG contrast ratio against the
+    /// `#RRGGBB` background color `bg`, returned in the same format.
+    pub fn auto_contrast_fg(&self, bg: &str) -> Result<String> {
+        let l = relative_luminance(parse_hex(bg)?);
+        let against_black = (l + 0.05) / 0.05;
+        let against_white = 1.05 / (l + 0.05);
+
+        let fg = if against_black >= against_white {
+            "#000000"
+        } else {
+            "#ffffff"
+        };
+
+        Ok(fg.to_string())
+    }
+}
+
+// XRenderColor channels are 16-bit so each 8-bit channel is scaled by 0x101 (c << 8 | c) in
+// order for 0xff to map to 0xffff. Anything creating an XRenderColor from 8-bit channels
+// should go through here.
+pub(crate) fn xrender_color([r, g, b, a]: [u8; 4]) -> XRenderColor {
+    XRenderColor {
+        red: channel16(r),
+        green: channel16(g),
+        blue: channel16(b),
+        alpha: channel16(a),
+    }
+}
+
+fn channel16(c: u8) -> u16 {
+    (c as u16) << 8 | c as u16
+}
+
+// WCAG relative luminance of an sRGB color
+fn relative_luminance([r, g, b]: [u8; 3]) -> f64 {
+    let linear = |c: u8| {
+        let c = c as f64 / 255.0;
+        if c <= 0.03928 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+
+    0.2126 * linear(r) + 0.7152 * linear(g) + 0.0722 * linear(b)
+}
+
+fn parse_hex(s: &str) -> Result<[u8; 3]> {
+    let err = || Error::InvalidHexColor(s.to_string());
+    let hex = s.strip_prefix('#').ok_or_else(err)?;
+    if hex.len() != 6 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(err());
+    }
+    let rgb = u32::from_str_radix(hex, 16).map_err(|_| err())?;
+    let [_, r, g, b] = rgb.to_be_bytes();
+
+    Ok([r, g, b])
+}
@@ -0,0 +1,165 @@
+//! The connection to the X server: querying it and detecting and recovering from its loss
+use crate::{create_drawable, open_display, Draw, Error, Result};
+use std::{ffi::CStr, mem};
+use x11::{
+    xlib::{XCloseDisplay, XConnectionNumber, XFreeGC, XFreePixmap, XServerVendor, XVendorRelease},
+    xrender::{XRenderQueryExtension, XRenderQueryVersion},
+};
+
+/// Details of the X server that a [Draw] is connected to, as returned by [Draw::server_info].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerInfo {
+    /// The vendor string of the server, e.g. "The X.Org Foundation"
+    pub vendor: String,
+    /// The vendor specific release number of the server
+    pub release: i32,
+    /// The (major, minor) version of the RENDER extension, if the server supports it
+    pub render_version: Option<(i32, i32)>,
+}
+
+impl Draw {
+    /// Query the vendor and release of the X server along with whether it supports the
+    /// RENDER extension, for working around server specific behaviour.
+    pub fn server_info(&self) -> ServerInfo {
+        self.assert_thread();
+
+        unsafe {
+            let vendor = XServerVendor(self.dpy);
+            let vendor = if vendor.is_null() {
+                String::new()
+            } else {
+                CStr::from_ptr(vendor).to_string_lossy().into_owned()
+            };
+
+            let (mut event_base, mut error_base) = (0, 0);
+            let (mut major, mut minor) = (0, 0);
+            let render_version =
+                (XRenderQueryExtension(self.dpy, &mut event_base, &mut error_base) != 0
+                    && XRenderQueryVersion(self.dpy, &mut major, &mut minor) != 0)
+                    .then_some((major, minor));
+
+            ServerInfo {
+                vendor,
+                release: XVendorRelease(self.dpy),
+                render_version,
+            }
+        }
+    }
+
+    /// Check whether the connection to the X server is still open without blocking.
+    pub fn is_connected(&self) -> bool {
+        self.assert_thread();
+
+        unsafe {
+            let fd = XConnectionNumber(self.dpy);
+            let mut pfd = libc::pollfd {
+                fd,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+
+            if libc::poll(&mut pfd, 1, 0) < 0 {
+                return false;
+            }
+            if pfd.revents & (libc::POLLERR | libc::POLLHUP | libc::POLLNVAL) != 0 {
+                return false;
+            }
+
+            // A readable socket with nothing to read means the server has closed it
+            if pfd.revents & libc::POLLIN != 0 {
+                let mut buf = [0u8; 1];
+                let flags = libc::MSG_PEEK | libc::MSG_DONTWAIT;
+                return libc::recv(fd, buf.as_mut_ptr() as *mut _, 1, flags) != 0;
+            }
+
+            true
+        }
+    }
+
+    /// Open a new connection to the X server, recreating the drawable, fonts and
+    /// colorschemes on it.
+    ///
+    /// The content of the drawable and any layers, the fill style, the target layer and any
+    /// background image are not carried over and need to be set again. If the old connection
+    /// is no longer open, the client side state associated with it is leaked rather than
+    /// freed as doing so would require talking to the server.
+    pub fn reconnect(&mut self) -> Result<()> {
+        self.assert_thread();
+        let dpy = open_display()?;
+
+        let vis = match unsafe { self.vis.reopen(dpy, self.root) } {
+            Some(vis) => vis,
+            None => {
+                unsafe { XCloseDisplay(dpy) };
+                return Err(Error::NoVisualForDepth(self.vis.depth));
+            }
+        };
+
+        let fs = match self.fs.reopen(dpy) {
+            Ok(fs) => fs,
+            Err(e) => {
+                unsafe {
+                    vis.free(dpy);
+                    XCloseDisplay(dpy);
+                }
+                return Err(e);
+            }
+        };
+
+        let schemes = match self.schemes.iter().map(|s| s.realloc(dpy, vis)).collect() {
+            Ok(schemes) => schemes,
+            Err(e) => {
+                drop(fs);
+                unsafe {
+                    vis.free(dpy);
+                    XCloseDisplay(dpy);
+                }
+                return Err(e);
+            }
+        };
+
+        let old_fs = mem::replace(&mut self.fs, fs);
+        let old_schemes = mem::replace(&mut self.schemes, schemes);
+        // Ad-hoc colors are reallocated against the new connection as they are next used
+        let old_colors = mem::take(&mut self.colors);
+
+        if self.is_connected() {
+            self.free_stipple();
+            self.clear_background_image();
+            self.free_layers();
+            drop(old_fs);
+            drop(old_schemes);
+            drop(old_colors);
+
+            unsafe {
+                if self.owns_drawable {
+                    XFreePixmap(self.dpy, self.drawable);
+                }
+                XFreeGC(self.dpy, self.gc);
+                self.vis.free(self.dpy);
+                XCloseDisplay(self.dpy);
+            }
+        } else {
+            // Any call using the old connection would trigger the Xlib IO error handler
+            mem::forget(old_fs);
+            mem::forget(old_schemes);
+            mem::forget(old_colors);
+            self.select_main_drawable();
+            self.stipple = 0;
+            self.bg_image = 0;
+        }
+
+        // A borrowed drawable may not have survived whatever broke the old connection so we
+        // always switch to one of our own
+        let (drawable, gc) = unsafe { create_drawable(dpy, self.root, vis, self.w, self.h) };
+        self.dpy = dpy;
+        self.vis = vis;
+        self.drawable = drawable;
+        self.owns_drawable = true;
+        self.gc = gc;
+        self.recreate_layers();
+        self.apply_clip();
+
+        Ok(())
+    }
+}
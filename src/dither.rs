@@ -0,0 +1,182 @@
+//! Ordered dithering for visuals that can't represent arbitrary colors
+use crate::{Draw, Error, Rect, Result};
+use std::{ffi::CString, mem::MaybeUninit};
+use x11::xlib::{
+    DirectColor, FillStippled, TrueColor, XColor, XCreateBitmapFromData, XFillRectangle,
+    XFreePixmap, XParseColor, XQueryColor, XQueryColors, XSetFillStyle, XSetForeground,
+    XSetStipple, XSetTSOrigin,
+};
+
+// 2x2 Bayer matrix in row-major order: cells are switched to the second color in this order
+const BAYER_2X2: [u8; 4] = [0, 2, 3, 1];
+
+// A pixel value in the colormap along with the RGB value stored for it
+type Cell = (u64, [u16; 3]);
+
+impl Draw {
+    /// Fill `r` with the named color, approximating it with a 2x2 ordered dither of the two
+    /// nearest colors in the colormap if the visual can't represent it exactly.
+    ///
+    /// The color is allocated (and cached as for [Draw::fill_rect_color]) if possible. When
+    /// that fails, or only gives an approximation, the existing colormap cells are used
+    /// without allocating them, so a dynamic colormap changing under us changes the result.
+    ///
+    /// On TrueColor and DirectColor visuals this is the same as [Draw::fill_rect_color].
+    pub fn fill_rect_dithered(&mut self, r: Rect, color: &str) -> Result<()> {
+        let class = unsafe { (*self.vis.visual).class };
+        if class == TrueColor || class == DirectColor {
+            return self.fill_rect_color(r, color);
+        }
+
+        let wanted = self.parse_color(color)?;
+        let exact = match self.color_pixel(color) {
+            Ok(pixel) if same_color(self.query_color(pixel), wanted) => Some(pixel),
+            // Failing to allocate the color is expected on a full PseudoColor colormap
+            _ => None,
+        };
+
+        let r = self.translate_rect(r);
+        self.ensure_fits(r)?;
+
+        if let Some(pixel) = exact {
+            self.fill_pixel(r, pixel);
+            return Ok(());
+        }
+
+        let (base, alt) = self.nearest_cells(wanted)?;
+        let n = match alt {
+            Some(alt) => alt_cells(wanted, base.1, alt.1),
+            None => 0,
+        };
+        let (base, alt) = (base.0, alt.map_or(base.0, |(p, _)| p));
+        if n == 0 || alt == base {
+            self.fill_pixel(r, base);
+            return Ok(());
+        } else if n == 4 {
+            self.fill_pixel(r, alt);
+            return Ok(());
+        }
+
+        let Some(Rect { x, y, w, h }) = r.clamped_to(self.w, self.h) else {
+            return Ok(());
+        };
+        self.fill_pixel(r, base);
+
+        let mut rows = [0u8; 2];
+        for (i, &v) in BAYER_2X2.iter().enumerate() {
+            if v < n {
+                rows[i / 2] |= 1 << (i % 2);
+            }
+        }
+
+        unsafe {
+            let pattern =
+                XCreateBitmapFromData(self.dpy, self.drawable, rows.as_ptr() as *const _, 2, 2);
+            if pattern == 0 {
+                return Err(Error::InvalidStipple { w: 2, h: 2 });
+            }
+
+            XSetStipple(self.dpy, self.gc, pattern);
+            XSetTSOrigin(self.dpy, self.gc, 0, 0);
+            XSetFillStyle(self.dpy, self.gc, FillStippled);
+            XSetForeground(self.dpy, self.gc, alt);
+            XFillRectangle(self.dpy, self.drawable, self.gc, x, y, w, h);
+            XFreePixmap(self.dpy, pattern);
+
+            if self.stipple != 0 {
+                XSetStipple(self.dpy, self.gc, self.stipple);
+            }
+        }
+        self.restore_fill_style();
+
+        Ok(())
+    }
+
+    // The two cells of the colormap closest to `wanted`. The second is None if the colormap
+    // only has a single cell.
+    fn nearest_cells(&self, wanted: [u16; 3]) -> Result<(Cell, Option<Cell>)> {
+        let n = unsafe { (*self.vis.visual).map_entries };
+        if n <= 0 {
+            return Err(Error::UnableToAllocateColor);
+        }
+
+        // SAFETY: an all-zero XColor is valid and XQueryColors only reads the pixels
+        let mut cells: Vec<XColor> = (0..n)
+            .map(|pixel| {
+                let mut xc: XColor = unsafe { MaybeUninit::zeroed().assume_init() };
+                xc.pixel = pixel as u64;
+                xc
+            })
+            .collect();
+        unsafe { XQueryColors(self.dpy, self.vis.colormap, cells.as_mut_ptr(), n) };
+
+        let mut cells: Vec<Cell> = cells
+            .into_iter()
+            .map(|xc| (xc.pixel, [xc.red, xc.green, xc.blue]))
+            .collect();
+        cells.sort_by_key(|&(_, rgb)| distance(rgb, wanted));
+
+        let mut nearest = cells.into_iter();
+        let base = nearest.next().ok_or(Error::UnableToAllocateColor)?;
+
+        Ok((base, nearest.next()))
+    }
+
+    // The exact 16-bit RGB value of a color spec, independent of what the colormap can hold
+    fn parse_color(&self, color: &str) -> Result<[u16; 3]> {
+        let c_color = CString::new(color)?;
+        let mut xc = MaybeUninit::<XColor>::zeroed();
+
+        let res = unsafe {
+            XParseColor(
+                self.dpy,
+                self.vis.colormap,
+                c_color.as_ptr(),
+                xc.as_mut_ptr(),
+            )
+        };
+        if res == 0 {
+            return Err(Error::UnableToAllocateColor);
+        }
+        // SAFETY: XParseColor has set the RGB values on success
+        let xc = unsafe { xc.assume_init() };
+
+        Ok([xc.red, xc.green, xc.blue])
+    }
+
+    // The RGB value actually stored in the colormap for an allocated pixel
+    fn query_color(&self, pixel: u64) -> [u16; 3] {
+        // SAFETY: an all-zero XColor is valid and XQueryColor only reads the pixel
+        let mut xc: XColor = unsafe { MaybeUninit::zeroed().assume_init() };
+        xc.pixel = pixel;
+        unsafe { XQueryColor(self.dpy, self.vis.colormap, &mut xc) };
+
+        [xc.red, xc.green, xc.blue]
+    }
+}
+
+// Colors are compared at 8 bits per channel as servers are free to round the values they
+// store in the colormap
+fn same_color(a: [u16; 3], b: [u16; 3]) -> bool {
+    a.map(|c| c >> 8) == b.map(|c| c >> 8)
+}
+
+fn distance(a: [u16; 3], b: [u16; 3]) -> i64 {
+    (0..3).map(|i| (a[i] as i64 - b[i] as i64).pow(2)).sum()
+}
+
+// How many of the four cells should use `b` in order to best approximate `wanted`, found by
+// projecting it onto the line between the two colors
+fn alt_cells(wanted: [u16; 3], a: [u16; 3], b: [u16; 3]) -> u8 {
+    let (mut dot, mut len) = (0.0, 0.0);
+    for i in 0..3 {
+        let ab = b[i] as f64 - a[i] as f64;
+        dot += (wanted[i] as f64 - a[i] as f64) * ab;
+        len += ab * ab;
+    }
+    if len == 0.0 {
+        return 0;
+    }
+
+    ((dot / len).clamp(0.0, 1.0) * 4.0).round() as u8
+}
@@ -0,0 +1,100 @@
+//! A retained list of draw commands that can be built once and replayed
+use crate::{Draw, Rect, Result};
+
+/// A single drawing operation that can be recorded in a [DrawList].
+///
+/// Each variant maps directly on to the [Draw] method of the same name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DrawCmd {
+    SetColorscheme(String),
+    DrawRect {
+        r: Rect,
+        invert: bool,
+    },
+    FillRect {
+        r: Rect,
+        invert: bool,
+    },
+    Line {
+        from: (i32, i32),
+        to: (i32, i32),
+        invert: bool,
+    },
+    DrawText {
+        txt: String,
+        lpad: u32,
+        r: Rect,
+        invert: bool,
+    },
+}
+
+/// An ordered list of [DrawCmd]s to be run against a [Draw] using [Draw::execute].
+///
+/// Lists can be compared against one another so that a scene only needs to be replayed
+/// when something in it has actually changed.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DrawList {
+    cmds: Vec<DrawCmd>,
+}
+
+impl DrawList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, cmd: DrawCmd) {
+        self.cmds.push(cmd);
+    }
+
+    pub fn clear(&mut self) {
+        self.cmds.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.cmds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cmds.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &DrawCmd> {
+        self.cmds.iter()
+    }
+}
+
+impl From<Vec<DrawCmd>> for DrawList {
+    fn from(cmds: Vec<DrawCmd>) -> Self {
+        Self { cmds }
+    }
+}
+
+impl FromIterator<DrawCmd> for DrawList {
+    fn from_iter<T: IntoIterator<Item = DrawCmd>>(iter: T) -> Self {
+        Self {
+            cmds: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl Draw {
+    /// Run each command in the given [DrawList] in order, stopping at the first error.
+    pub fn execute(&mut self, list: &DrawList) -> Result<()> {
+        for cmd in list.iter() {
+            match cmd {
+                DrawCmd::SetColorscheme(name) => self.set_colorscheme(name)?,
+                DrawCmd::DrawRect { r, invert } => self.draw_rect(*r, *invert)?,
+                DrawCmd::FillRect { r, invert } => self.fill_rect(*r, *invert)?,
+                DrawCmd::Line { from, to, invert } => self.draw_line(*from, *to, *invert)?,
+                DrawCmd::DrawText {
+                    txt,
+                    lpad,
+                    r,
+                    invert,
+                } => self.draw_text(txt, *lpad, *r, *invert)?,
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,82 @@
+//! Drawing text that arrives as raw bytes in one of the encodings used by X properties
+use crate::{Draw, Error, Rect, Result};
+use std::{ffi::CStr, os::raw::c_char};
+use x11::xlib::{False, XFreeStringList, XInternAtom, XTextProperty, Xutf8TextPropertyToTextList};
+
+/// The encoding of text passed to [Draw::draw_bytes].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Encoding {
+    /// UTF-8, as used by `_NET_WM_NAME`
+    Utf8,
+    /// ISO 8859-1, as used by `WM_NAME` with a type of `STRING`
+    Latin1,
+    /// X Compound Text, as used by `WM_NAME` with a type of `COMPOUND_TEXT`
+    CompoundText,
+}
+
+impl Draw {
+    /// Decode `bytes` using the given encoding and draw the resulting text in the same way as
+    /// [Draw::draw_text].
+    ///
+    /// This is intended for text read from window properties such as `WM_NAME` which may not
+    /// be UTF-8. Input that can not be fully converted returns [Error::UndecodableText].
+    pub fn draw_bytes(
+        &mut self,
+        bytes: &[u8],
+        encoding: Encoding,
+        lpad: u32,
+        r: Rect,
+        invert: bool,
+    ) -> Result<()> {
+        let txt = self.decode(bytes, encoding)?;
+
+        self.draw_text(&txt, lpad, r, invert)
+    }
+
+    fn decode(&self, bytes: &[u8], encoding: Encoding) -> Result<String> {
+        match encoding {
+            Encoding::Utf8 => std::str::from_utf8(bytes)
+                .map(|s| s.to_string())
+                .map_err(|_| Error::UndecodableText(encoding)),
+
+            // Latin1 code points map directly onto the first 256 unicode scalar values
+            Encoding::Latin1 => Ok(bytes.iter().map(|&b| b as char).collect()),
+
+            Encoding::CompoundText => unsafe { self.decode_compound_text(bytes) },
+        }
+    }
+
+    unsafe fn decode_compound_text(&self, bytes: &[u8]) -> Result<String> {
+        self.assert_thread();
+        if bytes.is_empty() {
+            return Ok(String::new());
+        }
+
+        let prop = XTextProperty {
+            value: bytes.as_ptr() as *mut _,
+            encoding: XInternAtom(self.dpy, c"COMPOUND_TEXT".as_ptr(), False),
+            format: 8,
+            nitems: bytes.len() as _,
+        };
+
+        let mut list: *mut *mut c_char = std::ptr::null_mut();
+        let mut count = 0;
+        // Zero is Success, negative values are errors and a positive value is the number of
+        // characters that could not be converted
+        let res = Xutf8TextPropertyToTextList(self.dpy, &prop, &mut list, &mut count);
+        if res != 0 || list.is_null() {
+            if !list.is_null() {
+                XFreeStringList(list);
+            }
+            return Err(Error::UndecodableText(Encoding::CompoundText));
+        }
+
+        // Embedded NULs in the property separate multiple strings
+        let parts: Vec<String> = (0..count as usize)
+            .map(|i| CStr::from_ptr(*list.add(i)).to_string_lossy().into_owned())
+            .collect();
+        XFreeStringList(list);
+
+        Ok(parts.join(" "))
+    }
+}
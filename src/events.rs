@@ -0,0 +1,43 @@
+//! Receiving events on the connection used for drawing
+use crate::Draw;
+use std::{mem::MaybeUninit, os::raw::c_long};
+use x11::xlib::{Window, XConnectionNumber, XEvent, XFlush, XNextEvent, XPending, XSelectInput};
+
+impl Draw {
+    /// Ask the X server to report the events in `mask` (e.g. `ExposureMask | ButtonPressMask`)
+    /// for the given window to this connection so that they can be read using
+    /// [Draw::poll_event]. This replaces any mask previously selected by this connection.
+    pub fn select_events(&mut self, win: u32, mask: c_long) {
+        self.assert_thread();
+
+        unsafe {
+            XSelectInput(self.dpy, win as Window, mask);
+            XFlush(self.dpy);
+        }
+    }
+
+    /// Return the next pending event for this connection without blocking, or `None` if
+    /// there are no events waiting.
+    pub fn poll_event(&mut self) -> Option<XEvent> {
+        self.assert_thread();
+
+        unsafe {
+            // XPending flushes the output buffer and reads anything available on the socket
+            if XPending(self.dpy) == 0 {
+                return None;
+            }
+
+            let mut ev = MaybeUninit::<XEvent>::uninit();
+            XNextEvent(self.dpy, ev.as_mut_ptr());
+
+            Some(ev.assume_init())
+        }
+    }
+
+    /// The file descriptor of the connection to the X server, for waiting on events alongside
+    /// other sources using select, poll or an async runtime. Once it is readable call
+    /// [Draw::poll_event] until it returns `None`.
+    pub fn connection_fd(&self) -> i32 {
+        unsafe { XConnectionNumber(self.dpy) }
+    }
+}
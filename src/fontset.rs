@@ -1,71 +1,426 @@
 use crate::{Error, Result, SCREEN};
 use fontconfig_sys::{
-    constants::{FC_CHARSET, FC_SCALABLE},
+    constants::{
+        FC_CHARSET, FC_DPI, FC_FAMILY, FC_LANG, FC_LCD_FILTER, FC_MONO, FC_PIXEL_SIZE, FC_RGBA,
+        FC_SCALABLE, FC_SIZE, FC_SPACING, FC_STYLE,
+    },
     FcCharSetAddChar, FcCharSetCreate, FcCharSetDestroy, FcConfig, FcConfigSubstitute,
-    FcDefaultSubstitute, FcMatchPattern, FcPatternAddBool, FcPatternAddCharSet, FcPatternDestroy,
-    FcPatternDuplicate,
+    FcDefaultSubstitute, FcInitReinitialize, FcMatchPattern, FcPatternAddBool, FcPatternAddCharSet,
+    FcPatternAddDouble, FcPatternAddInteger, FcPatternAddString, FcPatternDel, FcPatternDestroy,
+    FcPatternDuplicate, FcPatternGetDouble, FcPatternGetInteger, FcPatternGetString, FcResultMatch,
 };
 use std::{
     alloc::{alloc, handle_alloc_error, Layout},
     collections::HashMap,
-    ffi::CString,
+    ffi::{CStr, CString},
+    mem::MaybeUninit,
 };
 use x11::{
     xft::{
         FcPattern, FcResult, XftCharExists, XftFont, XftFontClose, XftFontMatch, XftFontOpenName,
         XftFontOpenPattern, XftNameParse, XftTextExtentsUtf8,
     },
-    xlib::Display,
+    xlib::{
+        Display, XCharStruct, XFontStruct, XFreeFont, XLoadQueryFont, XTextExtents, XTextWidth,
+    },
     xrender::XGlyphInfo,
 };
 
+/// The subpixel layout of the display, used by Xft for subpixel antialiasing (FC_RGBA).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subpixel {
+    None,
+    Rgb,
+    Bgr,
+    VerticalRgb,
+    VerticalBgr,
+}
+
+impl Subpixel {
+    // Values from fontconfig.h
+    fn fc_value(&self) -> i32 {
+        match self {
+            Self::Rgb => 1,
+            Self::Bgr => 2,
+            Self::VerticalRgb => 3,
+            Self::VerticalBgr => 4,
+            Self::None => 5,
+        }
+    }
+}
+
+/// The filter applied to subpixel rendered glyphs to reduce color fringing (FC_LCD_FILTER).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LcdFilter {
+    None,
+    Default,
+    Light,
+    Legacy,
+}
+
+impl LcdFilter {
+    // Values from fontconfig.h
+    fn fc_value(&self) -> i32 {
+        match self {
+            Self::None => 0,
+            Self::Default => 1,
+            Self::Light => 2,
+            Self::Legacy => 3,
+        }
+    }
+}
+
+/// How text is rendered.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// Antialiased client side rendering of fontconfig fonts using Xft and the RENDER
+    /// extension, with fallback fonts for characters missing from the primary font.
+    #[default]
+    Xft,
+    /// Server side rendering of core X11 fonts given as XLFD names (e.g. "fixed" or
+    /// "-misc-fixed-*"). This works on servers without RENDER and is cheap over remote
+    /// connections but has no antialiasing, no fallback fonts and only supports Latin-1:
+    /// any other characters are drawn as '?'. [FontOptions] have no effect.
+    Core,
+}
+
+/// Additional properties to set on the font pattern before it is matched by fontconfig.
+///
+/// Any option left as `None` is left for fontconfig to decide.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct FontOptions {
+    pub subpixel: Option<Subpixel>,
+    pub lcd_filter: Option<LcdFilter>,
+    /// Point size overriding any size or pixelsize given in the font name
+    pub size: Option<f64>,
+    /// Fail with [Error::FontSubstituted] rather than silently using whichever font
+    /// fontconfig substitutes when the requested family is not installed
+    pub strict: bool,
+}
+
+impl FontOptions {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    unsafe fn apply(&self, pat: *mut FcPattern) {
+        let int_props: [(&CStr, Option<i32>); 2] = [
+            (FC_RGBA.as_cstr(), self.subpixel.map(|s| s.fc_value())),
+            (
+                FC_LCD_FILTER.as_cstr(),
+                self.lcd_filter.map(|f| f.fc_value()),
+            ),
+        ];
+
+        for (prop, val) in int_props {
+            if let Some(val) = val {
+                FcPatternDel(pat as *mut _, prop.as_ptr());
+                FcPatternAddInteger(pat as *mut _, prop.as_ptr(), val);
+            }
+        }
+
+        if let Some(size) = self.size {
+            // pixelsize takes precedence over size if both are set
+            FcPatternDel(pat as *mut _, FC_PIXEL_SIZE.as_ptr());
+            FcPatternDel(pat as *mut _, FC_SIZE.as_ptr());
+            FcPatternAddDouble(pat as *mut _, FC_SIZE.as_ptr(), size);
+        }
+    }
+}
+
+// Reload the default fontconfig configuration and rescan the font directories
+pub(crate) fn reinitialize_fontconfig() -> Result<()> {
+    if unsafe { FcInitReinitialize() } == 0 {
+        return Err(Error::UnableToReinitializeFontconfig);
+    }
+
+    Ok(())
+}
+
+// Whether matching the given font name results in the requested family rather than whatever
+// fontconfig substitutes for a missing font. Names without a family are always available.
+pub(crate) fn font_available(dpy: *mut Display, name: &str) -> bool {
+    let c_name = match CString::new(name) {
+        Ok(c_name) => c_name,
+        Err(_) => return false,
+    };
+
+    unsafe {
+        let pattern = XftNameParse(c_name.as_ptr());
+        if pattern.is_null() {
+            return false;
+        }
+
+        let mut res = MaybeUninit::<FcResult>::uninit();
+        let font_match = XftFontMatch(dpy, SCREEN, pattern, res.as_mut_ptr());
+        let available = !font_match.is_null() && substituted_family(pattern, font_match).is_none();
+
+        FcPatternDestroy(pattern as *mut _);
+        if !font_match.is_null() {
+            FcPatternDestroy(font_match as *mut _);
+        }
+
+        available
+    }
+}
+
+// The requested and matched families if fontconfig substituted a different family for the
+// one that was requested
+unsafe fn substituted_family(
+    requested: *mut FcPattern,
+    matched: *mut FcPattern,
+) -> Option<(String, String)> {
+    let requested = pattern_family(requested)?;
+    let matched = pattern_family(matched).unwrap_or_default();
+
+    (!requested.eq_ignore_ascii_case(&matched)).then_some((requested, matched))
+}
+
+unsafe fn pattern_family(pattern: *mut FcPattern) -> Option<String> {
+    pattern_string(pattern, FC_FAMILY.as_cstr())
+}
+
+unsafe fn pattern_string(pattern: *mut FcPattern, key: &CStr) -> Option<String> {
+    let mut val = std::ptr::null_mut();
+    let res = FcPatternGetString(pattern as *mut _, key.as_ptr(), 0, &mut val);
+    if res != FcResultMatch || val.is_null() {
+        return None;
+    }
+
+    Some(
+        CStr::from_ptr(val as *const _)
+            .to_string_lossy()
+            .into_owned(),
+    )
+}
+
+/// The concrete font that a font name resolved to, as returned by
+/// [Draw::font_description][crate::Draw::font_description].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontDescription {
+    /// The family name of the matched font, or the font name for core X11 fonts
+    pub family: String,
+    /// The style of the matched font, such as "Regular" or "Bold Italic". This is empty for
+    /// core X11 fonts.
+    pub style: String,
+    /// The pixel size of the matched font, or its height for core X11 fonts
+    pub pixel_size: f64,
+}
+
 #[derive(Debug)]
 pub(crate) struct Fontset {
     dpy: *mut Display,
+    backend: Backend,
+    name: String,
+    opts: FontOptions,
+    lang: Option<CString>,
     primary: Font,
     fallback: Vec<Font>,
+    ranges: Vec<RangeFont>,
     char_cache: HashMap<char, FontMatch>,
+    space_width: Option<i32>,
+    closed: bool,
+}
+
+// A font explicitly requested for a range of characters. The font itself is stored with the
+// other fallback fonts so that it shares their indices.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RangeFont {
+    start: char,
+    end: char,
+    pattern: String,
+    index: usize,
 }
 
 impl Fontset {
-    pub(crate) fn try_new(dpy: *mut Display, fnt: &str) -> Result<Self> {
+    pub(crate) fn try_new(
+        dpy: *mut Display,
+        fnt: &str,
+        opts: FontOptions,
+        backend: Backend,
+    ) -> Result<Self> {
+        let primary = match backend {
+            Backend::Xft => Font::try_new_from_name(dpy, fnt, &opts)?,
+            Backend::Core => Font::try_new_core(dpy, fnt)?,
+        };
+
         Ok(Self {
             dpy,
-            primary: Font::try_new_from_name(dpy, fnt)?,
+            backend,
+            name: fnt.to_string(),
+            opts,
+            lang: None,
+            primary,
             fallback: Default::default(),
+            ranges: Default::default(),
             char_cache: Default::default(),
+            space_width: None,
+            closed: false,
         })
     }
 
+    // Load a new Fontset with the same range fonts as this one
+    pub(crate) fn reload(&self, fnt: &str, opts: FontOptions) -> Result<Self> {
+        self.reload_on(self.dpy, fnt, opts)
+    }
+
+    // Load the same fonts again on a different connection
+    pub(crate) fn reopen(&self, dpy: *mut Display) -> Result<Self> {
+        self.reload_on(dpy, &self.name, self.opts)
+    }
+
+    fn reload_on(&self, dpy: *mut Display, fnt: &str, opts: FontOptions) -> Result<Self> {
+        let mut fs = Self::try_new(dpy, fnt, opts, self.backend)?;
+        fs.lang = self.lang.clone();
+        for r in self.ranges.iter() {
+            fs.add_range_font(r.start, r.end, &r.pattern)?;
+        }
+
+        Ok(fs)
+    }
+
+    pub(crate) fn add_range_font(&mut self, start: char, end: char, pattern: &str) -> Result<()> {
+        if start > end {
+            return Err(Error::InvalidCharRange { start, end });
+        }
+
+        let fnt = Font::try_new_from_name(self.dpy, pattern, &self.opts)?;
+        self.fallback.push(fnt);
+        self.ranges.push(RangeFont {
+            start,
+            end,
+            pattern: pattern.to_string(),
+            index: self.fallback.len() - 1,
+        });
+        self.char_cache.clear();
+
+        Ok(())
+    }
+
+    // Load a new Fontset that prefers fallback fonts for the given language, or that has no
+    // language preference if lang is None
+    pub(crate) fn with_language(&self, lang: Option<&str>) -> Result<Self> {
+        let lang = lang.map(CString::new).transpose()?;
+        let mut fs = self.reload(&self.name, self.opts)?;
+        fs.lang = lang;
+
+        Ok(fs)
+    }
+
+    pub(crate) fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub(crate) fn opts(&self) -> FontOptions {
+        self.opts
+    }
+
     // Find boundaries where we need to change the font we are using for rendering utf8
     // characters from the given input.
     pub(crate) fn per_font_chunks<'a>(&mut self, txt: &'a str) -> Vec<(&'a str, FontMatch)> {
-        let mut char_indices = txt.char_indices();
         let mut chunks = Vec::new();
-        let mut last_split = 0;
-        let mut chunk: &str;
-        let mut rest = txt;
+        self.extend_font_chunks(txt, &mut chunks);
 
-        let mut cur_fm = match char_indices.next() {
-            Some((_, c)) => self.fnt_for_char(c),
-            None => return chunks, // empty string: no chunks
-        };
+        chunks
+    }
+
+    // As per_font_chunks but appending to an existing buffer so that it can be reused
+    pub(crate) fn extend_font_chunks<'a>(
+        &mut self,
+        txt: &'a str,
+        buf: &mut Vec<(&'a str, FontMatch)>,
+    ) {
+        let mut chunks = self.chunks(txt);
+        buf.extend(std::iter::from_fn(|| chunks.next_match()));
+    }
+
+    pub(crate) fn chunks<'a, 'b>(&'b mut self, txt: &'a str) -> FontChunks<'a, 'b> {
+        FontChunks {
+            fs: self,
+            rest: txt,
+        }
+    }
 
-        for (i, c) in char_indices {
-            let fm = self.fnt_for_char(c);
-            if fm != cur_fm {
-                (chunk, rest) = rest.split_at(i - last_split);
-                chunks.push((chunk, cur_fm));
-                cur_fm = fm;
-                last_split = i;
+    pub(crate) fn description(&self) -> FontDescription {
+        if self.primary.is_core() {
+            return FontDescription {
+                family: self.name.clone(),
+                style: String::new(),
+                pixel_size: self.primary.h as f64,
+            };
+        }
+
+        // SAFETY: the pattern of an open Xft font is valid for as long as the font is
+        unsafe {
+            let pattern = (*self.primary.xfont).pattern;
+            let mut pixel_size = 0.0;
+            let res = FcPatternGetDouble(
+                pattern as *mut _,
+                FC_PIXEL_SIZE.as_ptr(),
+                0,
+                &mut pixel_size,
+            );
+
+            FontDescription {
+                family: pattern_family(pattern).unwrap_or_default(),
+                style: pattern_string(pattern, FC_STYLE.as_cstr()).unwrap_or_default(),
+                pixel_size: if res == FcResultMatch {
+                    pixel_size
+                } else {
+                    self.primary.h as f64
+                },
             }
         }
+    }
 
-        if !rest.is_empty() {
-            chunks.push((rest, cur_fm));
+    // The advance of U+0020 in the primary font, measured once and then cached
+    pub(crate) fn space_width(&mut self) -> Result<i32> {
+        if let Some(w) = self.space_width {
+            return Ok(w);
         }
 
-        chunks
+        let w = self.primary.glyph_info(self.dpy, " ")?.xOff as i32;
+        self.space_width = Some(w);
+
+        Ok(w)
+    }
+
+    // Whether the primary font declares itself as monospace (or charcell) to fontconfig,
+    // falling back to comparing the advances of a few glyphs with very different widths
+    // for core fonts and patterns without a spacing
+    pub(crate) fn is_monospace(&self) -> bool {
+        if !self.primary.is_core() {
+            let mut spacing = 0;
+            // SAFETY: the pattern of an open Xft font is valid for as long as the font is
+            let res = unsafe {
+                FcPatternGetInteger(
+                    (*self.primary.xfont).pattern as *mut _,
+                    FC_SPACING.as_ptr(),
+                    0,
+                    &mut spacing,
+                )
+            };
+            if res == FcResultMatch {
+                return spacing >= FC_MONO;
+            }
+        }
+
+        let mut advances = ["i", "M", "W", "."]
+            .into_iter()
+            .map(|s| self.primary.glyph_info(self.dpy, s).map(|g| g.xOff));
+
+        match advances.next() {
+            Some(Ok(first)) => advances.all(|a| matches!(a, Ok(a) if a == first)),
+            _ => false,
+        }
+    }
+
+    pub(crate) fn primary(&self) -> &Font {
+        &self.primary
     }
 
     pub(crate) fn fnt(&self, fm: FontMatch) -> &Font {
@@ -75,11 +430,32 @@ impl Fontset {
         }
     }
 
+    // The font at a flat index as returned by FontMatch::index
+    pub(crate) fn font_match(&self, index: usize) -> Result<FontMatch> {
+        match index {
+            0 => Ok(FontMatch::Primary),
+            n if n <= self.fallback.len() => Ok(FontMatch::Fallback(n - 1)),
+            _ => Err(Error::FontIndexOutOfBounds {
+                index,
+                len: self.fallback.len() + 1,
+            }),
+        }
+    }
+
     fn fnt_for_char(&mut self, c: char) -> FontMatch {
         if let Some(fm) = self.char_cache.get(&c) {
             return *fm;
         }
 
+        let range_match = self.ranges.iter().find(|r| {
+            (r.start..=r.end).contains(&c) && self.fallback[r.index].contains_char(self.dpy, c)
+        });
+        if let Some(r) = range_match {
+            let fm = FontMatch::Fallback(r.index);
+            self.char_cache.insert(c, fm);
+            return fm;
+        }
+
         if self.primary.contains_char(self.dpy, c) {
             self.char_cache.insert(c, FontMatch::Primary);
             return FontMatch::Primary;
@@ -92,15 +468,17 @@ impl Fontset {
             }
         }
 
-        let fallback = match self.primary.fallback_for_char(self.dpy, c) {
+        let fallback = match self
+            .primary
+            .fallback_for_char(self.dpy, c, self.lang.as_deref())
+        {
             Ok(fnt) => {
                 self.fallback.push(fnt);
                 FontMatch::Fallback(self.fallback.len() - 1)
             }
 
             Err(e) => {
-                // TODO: add tracing to this crate
-                println!("ERROR: {e}");
+                log_error!("{e}");
                 FontMatch::Primary
             }
         };
@@ -111,15 +489,115 @@ impl Fontset {
     }
 }
 
-impl Drop for Fontset {
-    fn drop(&mut self) {
+impl Fontset {
+    // Idempotent so that the parent draw can close fonts before closing the display
+    pub(crate) fn close(&mut self) {
+        if self.closed {
+            return;
+        }
+
         // SAFETY: the Display we have a pointer to is freed by the parent draw
         unsafe {
-            XftFontClose(self.dpy, self.primary.xfont);
+            self.primary.close(self.dpy);
             for f in self.fallback.drain(0..) {
-                XftFontClose(self.dpy, f.xfont);
+                f.close(self.dpy);
             }
         }
+        self.closed = true;
+    }
+}
+
+impl Drop for Fontset {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/// An iterator over contiguous slices of a string that are rendered using the same font,
+/// returned by [Draw::font_chunks][crate::Draw::font_chunks].
+///
+/// Each chunk is paired with the index of the font used to render it, with the primary
+/// font at index 0.
+#[derive(Debug)]
+pub struct FontChunks<'a, 'b> {
+    fs: &'b mut Fontset,
+    rest: &'a str,
+}
+
+impl<'a, 'b> FontChunks<'a, 'b> {
+    fn next_match(&mut self) -> Option<(&'a str, FontMatch)> {
+        let mut char_indices = self.rest.char_indices();
+        let cur_fm = match char_indices.next() {
+            Some((_, c)) => self.fs.fnt_for_char(c),
+            None => return None,
+        };
+
+        let split = char_indices
+            .find(|&(_, c)| self.fs.fnt_for_char(c) != cur_fm)
+            .map(|(i, _)| i)
+            .unwrap_or(self.rest.len());
+
+        let (chunk, rest) = self.rest.split_at(split);
+        self.rest = rest;
+
+        Some((chunk, cur_fm))
+    }
+}
+
+/// A borrowed handle to one of the fonts loaded by a [Draw][crate::Draw], as passed to the
+/// callback of [Draw::for_each_chunk][crate::Draw::for_each_chunk].
+#[derive(Debug, Clone, Copy)]
+pub struct FontHandle<'a> {
+    dpy: *mut Display,
+    fnt: &'a Font,
+    index: usize,
+}
+
+impl<'a> FontHandle<'a> {
+    pub(crate) fn new(dpy: *mut Display, fnt: &'a Font, fm: FontMatch) -> Self {
+        Self {
+            dpy,
+            fnt,
+            index: fm.index(),
+        }
+    }
+
+    /// The index of this font, with the primary font at index 0. This can be passed to
+    /// [Draw::draw_text_in_font][crate::Draw::draw_text_in_font].
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The height (ascent + descent) of this font.
+    pub fn height(&self) -> i32 {
+        self.fnt.h
+    }
+
+    /// The distance from the top of a line to the baseline for this font.
+    pub fn ascent(&self) -> i32 {
+        self.fnt.ascent()
+    }
+
+    /// The matched point size of this font, or `None` for core X11 fonts.
+    pub fn size(&self) -> Option<f64> {
+        self.fnt.size()
+    }
+
+    /// The width and height of `txt` when rendered using only this font.
+    pub fn text_extent(&self, txt: &str) -> Result<(i32, i32)> {
+        if txt.is_empty() {
+            return Ok((0, 0));
+        }
+
+        self.fnt.get_exts(self.dpy, txt)
+    }
+}
+
+impl<'a, 'b> Iterator for FontChunks<'a, 'b> {
+    type Item = (&'a str, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_match().map(|(chunk, fm)| (chunk, fm.index()))
     }
 }
 
@@ -129,39 +607,126 @@ pub(crate) enum FontMatch {
     Fallback(usize),
 }
 
+impl FontMatch {
+    // A flat index over all loaded fonts with the primary font at 0
+    pub(crate) fn index(&self) -> usize {
+        match self {
+            Self::Primary => 0,
+            Self::Fallback(n) => n + 1,
+        }
+    }
+}
+
 // Fonts contain a resource that requires a Display to free on Drop so they
 // are owned by their parent Draw and cleaned up when the Draw is dropped
 //
 // https://man.archlinux.org/man/extra/libxft/XftFontMatch.3.en
 // https://refspecs.linuxfoundation.org/fontconfig-2.6.0/index.html
+//
+// Core fonts have a null xfont and pattern and Xft fonts have a null core font.
 #[derive(Debug)]
 pub(crate) struct Font {
     pub(crate) h: i32,
     pub(crate) xfont: *mut XftFont,
+    pub(crate) core: *mut XFontStruct,
     pattern: *mut FcPattern,
 }
 
 impl Font {
-    fn try_new_from_name(dpy: *mut Display, name: &str) -> Result<Self> {
+    fn try_new_from_name(dpy: *mut Display, name: &str, opts: &FontOptions) -> Result<Self> {
+        let fnt = if opts.is_default() {
+            Self::try_new_from_name_without_options(dpy, name)?
+        } else {
+            Self::try_new_from_name_with_options(dpy, name, opts)?
+        };
+
+        log_debug!("opened font '{name}' with height {}", fnt.h);
+
+        if opts.strict {
+            // SAFETY: both patterns are valid for as long as the font is open
+            let substituted = unsafe { substituted_family(fnt.pattern, (*fnt.xfont).pattern) };
+            if let Some((requested, got)) = substituted {
+                unsafe {
+                    fnt.close(dpy);
+                    FcPatternDestroy(fnt.pattern as *mut _);
+                }
+                return Err(Error::FontSubstituted { requested, got });
+            }
+        }
+
+        Ok(fnt)
+    }
+
+    fn try_new_from_name_without_options(dpy: *mut Display, name: &str) -> Result<Self> {
         let (xfont, pattern, h) = unsafe {
+            // Parse first so that syntax errors are reported separately from missing fonts
             let c_name = CString::new(name)?;
+            let pattern = XftNameParse(c_name.as_ptr());
+            if pattern.is_null() {
+                return Err(Error::UnableToParseFontPattern(name.to_string()));
+            }
+
             let xfont = XftFontOpenName(dpy, SCREEN, c_name.as_ptr());
             if xfont.is_null() {
+                FcPatternDestroy(pattern as *mut _);
                 return Err(Error::UnableToOpenFont(name.to_string()));
             }
 
+            let h = (*xfont).ascent + (*xfont).descent;
+
+            (xfont, pattern, h)
+        };
+
+        Ok(Font {
+            xfont,
+            core: std::ptr::null_mut(),
+            pattern,
+            h,
+        })
+    }
+
+    // XftFontOpenName parses and matches the name in one go so there is no opportunity to set
+    // additional properties: we need to run the match ourselves against the modified pattern.
+    // The modified pattern is kept so that fallback fonts pick up the same options.
+    fn try_new_from_name_with_options(
+        dpy: *mut Display,
+        name: &str,
+        opts: &FontOptions,
+    ) -> Result<Self> {
+        let (xfont, pattern, h) = unsafe {
+            let c_name = CString::new(name)?;
             let pattern = XftNameParse(c_name.as_ptr());
             if pattern.is_null() {
-                XftFontClose(dpy, xfont);
                 return Err(Error::UnableToParseFontPattern(name.to_string()));
             }
+            opts.apply(pattern);
+
+            let mut res = MaybeUninit::<FcResult>::uninit();
+            let font_match = XftFontMatch(dpy, SCREEN, pattern, res.as_mut_ptr());
+            if font_match.is_null() {
+                FcPatternDestroy(pattern as *mut _);
+                return Err(Error::UnableToOpenFont(name.to_string()));
+            }
+
+            // On success the matched pattern is owned by the font
+            let xfont = XftFontOpenPattern(dpy, font_match);
+            if xfont.is_null() {
+                FcPatternDestroy(font_match as *mut _);
+                FcPatternDestroy(pattern as *mut _);
+                return Err(Error::UnableToOpenFont(name.to_string()));
+            }
 
             let h = (*xfont).ascent + (*xfont).descent;
 
             (xfont, pattern, h)
         };
 
-        Ok(Font { xfont, pattern, h })
+        Ok(Font {
+            xfont,
+            core: std::ptr::null_mut(),
+            pattern,
+            h,
+        })
     }
 
     fn try_new_from_pattern(dpy: *mut Display, pattern: *mut FcPattern) -> Result<Self> {
@@ -176,44 +741,177 @@ impl Font {
             (xfont, h)
         };
 
-        Ok(Font { xfont, pattern, h })
+        Ok(Font {
+            xfont,
+            core: std::ptr::null_mut(),
+            pattern,
+            h,
+        })
     }
 
-    fn contains_char(&self, dpy: *mut Display, c: char) -> bool {
-        unsafe { XftCharExists(dpy, self.xfont, c as u32) == 1 }
+    fn try_new_core(dpy: *mut Display, name: &str) -> Result<Self> {
+        let c_name = CString::new(name)?;
+        let core = unsafe { XLoadQueryFont(dpy, c_name.as_ptr()) };
+        if core.is_null() {
+            return Err(Error::UnableToOpenFont(name.to_string()));
+        }
+        log_debug!("opened core font '{name}'");
+
+        Ok(Font {
+            xfont: std::ptr::null_mut(),
+            core,
+            pattern: std::ptr::null_mut(),
+            h: unsafe { (*core).ascent + (*core).descent },
+        })
     }
 
-    pub(crate) fn get_exts(&self, dpy: *mut Display, txt: &str) -> Result<(i32, i32)> {
+    pub(crate) fn is_core(&self) -> bool {
+        !self.core.is_null()
+    }
+
+    pub(crate) fn ascent(&self) -> i32 {
         unsafe {
-            // https://doc.rust-lang.org/std/alloc/trait.GlobalAlloc.html#tymethod.alloc
-            let layout = Layout::new::<XGlyphInfo>();
-            let ptr = alloc(layout);
-            if ptr.is_null() {
-                handle_alloc_error(layout);
+            if self.is_core() {
+                (*self.core).ascent
+            } else {
+                (*self.xfont).ascent
             }
-            let ext = ptr as *mut XGlyphInfo;
+        }
+    }
 
-            let c_str = CString::new(txt)?;
+    unsafe fn close(&self, dpy: *mut Display) {
+        if self.is_core() {
+            XFreeFont(dpy, self.core);
+        } else {
+            XftFontClose(dpy, self.xfont);
+        }
+    }
+
+    /// The point size of the font that was actually matched by fontconfig. Fonts that were
+    /// only given a pixelsize are converted to points using the dpi of the pattern.
+    pub(crate) fn size(&self) -> Option<f64> {
+        if self.is_core() {
+            return None;
+        }
+
+        // SAFETY: the pattern of an open Xft font is valid for as long as the font is
+        let get = |key: &CStr| unsafe {
+            let mut val = 0.0;
+            let res =
+                FcPatternGetDouble((*self.xfont).pattern as *mut _, key.as_ptr(), 0, &mut val);
+
+            (res == FcResultMatch).then_some(val)
+        };
+
+        get(FC_SIZE.as_cstr()).or_else(|| {
+            // fontconfig assumes 75dpi when the pattern doesn't specify one
+            let dpi = get(FC_DPI.as_cstr()).unwrap_or(75.0);
+            get(FC_PIXEL_SIZE.as_cstr()).map(|px| px * 72.0 / dpi)
+        })
+    }
+
+    // Core fonts claim every character as they have no fallback
+    fn contains_char(&self, dpy: *mut Display, c: char) -> bool {
+        self.is_core() || unsafe { XftCharExists(dpy, self.xfont, c as u32) == 1 }
+    }
+
+    // The advance width of txt, which must already have been checked for nul bytes
+    pub(crate) fn advance(&self, dpy: *mut Display, txt: &str) -> i32 {
+        if self.is_core() {
+            let bytes = latin1(txt);
+            return unsafe {
+                XTextWidth(self.core, bytes.as_ptr() as *const _, bytes.len() as i32)
+            };
+        }
+
+        let mut ext = MaybeUninit::<XGlyphInfo>::uninit();
+        unsafe {
+            XftTextExtentsUtf8(
+                dpy,
+                self.xfont,
+                txt.as_ptr(),
+                txt.len() as i32,
+                ext.as_mut_ptr(),
+            );
+            ext.assume_init().xOff as i32
+        }
+    }
+
+    pub(crate) fn get_exts(&self, dpy: *mut Display, txt: &str) -> Result<(i32, i32)> {
+        if self.is_core() {
+            let bytes = latin1(txt);
+            let w =
+                unsafe { XTextWidth(self.core, bytes.as_ptr() as *const _, bytes.len() as i32) };
+
+            return Ok((w, self.h));
+        }
+
+        let info = self.glyph_info(dpy, txt)?;
+
+        Ok((info.xOff as i32, self.h))
+    }
+
+    // The ink bounding box and advance of txt, relative to the origin of its baseline
+    pub(crate) fn glyph_info(&self, dpy: *mut Display, txt: &str) -> Result<XGlyphInfo> {
+        if self.is_core() {
+            let bytes = latin1(txt);
+            let (mut dir, mut ascent, mut descent) = (0, 0, 0);
+            let mut overall = MaybeUninit::<XCharStruct>::zeroed();
+            let o = unsafe {
+                XTextExtents(
+                    self.core,
+                    bytes.as_ptr() as *const _,
+                    bytes.len() as i32,
+                    &mut dir,
+                    &mut ascent,
+                    &mut descent,
+                    overall.as_mut_ptr(),
+                );
+                overall.assume_init()
+            };
+
+            // Xft reports the bearing as the distance from the left edge of the ink to the
+            // origin where XCharStruct has the distance from the origin to the ink
+            return Ok(XGlyphInfo {
+                width: (o.rbearing - o.lbearing).max(0) as u16,
+                height: (o.ascent + o.descent).max(0) as u16,
+                x: -o.lbearing,
+                y: o.ascent,
+                xOff: o.width,
+                yOff: 0,
+            });
+        }
+
+        let c_str = CString::new(txt)?;
+        let mut ext = MaybeUninit::<XGlyphInfo>::uninit();
+
+        unsafe {
             XftTextExtentsUtf8(
                 dpy,
                 self.xfont,
                 c_str.as_ptr() as *mut u8,
                 c_str.as_bytes().len() as i32,
-                ext,
+                ext.as_mut_ptr(),
             );
 
-            Ok(((*ext).xOff as i32, self.h))
+            Ok(ext.assume_init())
         }
     }
 
-    /// Find a font that can handle a given character using fontconfig and this font's pattern
-    fn fallback_for_char(&self, dpy: *mut Display, c: char) -> Result<Self> {
-        let pat = self.fc_font_match(dpy, c)?;
+    /// Find a font that can handle a given character using fontconfig and this font's pattern,
+    /// preferring fonts that support the given language if there is one
+    fn fallback_for_char(&self, dpy: *mut Display, c: char, lang: Option<&CStr>) -> Result<Self> {
+        let pat = self.fc_font_match(dpy, c, lang)?;
 
         Font::try_new_from_pattern(dpy, pat)
     }
 
-    fn fc_font_match(&self, dpy: *mut Display, c: char) -> Result<*mut FcPattern> {
+    fn fc_font_match(
+        &self,
+        dpy: *mut Display,
+        c: char,
+        lang: Option<&CStr>,
+    ) -> Result<*mut FcPattern> {
         unsafe {
             let charset = FcCharSetCreate();
             FcCharSetAddChar(charset, c as u32);
@@ -221,6 +919,10 @@ impl Font {
             let pat = FcPatternDuplicate(self.pattern as *const _);
             FcPatternAddCharSet(pat, FC_CHARSET.as_ptr(), charset);
             FcPatternAddBool(pat, FC_SCALABLE.as_ptr(), 1); // FcTrue=1
+            if let Some(lang) = lang {
+                FcPatternDel(pat, FC_LANG.as_ptr());
+                FcPatternAddString(pat, FC_LANG.as_ptr(), lang.as_ptr() as *const _);
+            }
 
             FcConfigSubstitute(std::ptr::null::<FcConfig>() as *mut _, pat, FcMatchPattern);
             FcDefaultSubstitute(pat);
@@ -247,3 +949,10 @@ impl Font {
         }
     }
 }
+
+// Core fonts are indexed by single bytes so anything outside of Latin-1 is replaced
+pub(crate) fn latin1(txt: &str) -> Vec<u8> {
+    txt.chars()
+        .map(|c| u8::try_from(c as u32).unwrap_or(b'?'))
+        .collect()
+}
@@ -0,0 +1,80 @@
+//! A chainable wrapper around [Draw] for writing render code as a pipeline
+use crate::{Draw, Rect, Result};
+
+/// A mutable borrow of a [Draw] whose methods return the frame again so that calls can be
+/// chained with `?`. Create one using [Draw::frame].
+///
+/// Each method behaves exactly like the [Draw] method of the same name.
+#[derive(Debug)]
+pub struct Frame<'a> {
+    drw: &'a mut Draw,
+}
+
+impl Draw {
+    /// Borrow this [Draw] as a [Frame] for chaining draw calls.
+    pub fn frame(&mut self) -> Frame<'_> {
+        Frame { drw: self }
+    }
+}
+
+impl<'a> Frame<'a> {
+    pub fn set_colorscheme(&mut self, scheme: &str) -> Result<&mut Self> {
+        self.drw.set_colorscheme(scheme)?;
+        Ok(self)
+    }
+
+    pub fn reset_colorscheme(&mut self) -> &mut Self {
+        self.drw.reset_colorscheme();
+        self
+    }
+
+    pub fn push_translate(&mut self, dx: i32, dy: i32) -> &mut Self {
+        self.drw.push_translate(dx, dy);
+        self
+    }
+
+    pub fn pop_translate(&mut self) -> &mut Self {
+        self.drw.pop_translate();
+        self
+    }
+
+    pub fn clear(&mut self) -> Result<&mut Self> {
+        self.drw.clear()?;
+        Ok(self)
+    }
+
+    pub fn draw_rect(&mut self, r: Rect, invert: bool) -> Result<&mut Self> {
+        self.drw.draw_rect(r, invert)?;
+        Ok(self)
+    }
+
+    pub fn fill_rect(&mut self, r: Rect, invert: bool) -> Result<&mut Self> {
+        self.drw.fill_rect(r, invert)?;
+        Ok(self)
+    }
+
+    pub fn fill_rect_color(&mut self, r: Rect, color: &str) -> Result<&mut Self> {
+        self.drw.fill_rect_color(r, color)?;
+        Ok(self)
+    }
+
+    pub fn draw_line(
+        &mut self,
+        from: (i32, i32),
+        to: (i32, i32),
+        invert: bool,
+    ) -> Result<&mut Self> {
+        self.drw.draw_line(from, to, invert)?;
+        Ok(self)
+    }
+
+    pub fn draw_text(&mut self, txt: &str, lpad: u32, r: Rect, invert: bool) -> Result<&mut Self> {
+        self.drw.draw_text(txt, lpad, r, invert)?;
+        Ok(self)
+    }
+
+    pub fn flush_to(&mut self, win: u32, r: Rect) -> Result<&mut Self> {
+        self.drw.flush_to(win, r)?;
+        Ok(self)
+    }
+}
@@ -0,0 +1,586 @@
+//! Blitting client side RGBA pixel data into the drawable
+use crate::{color::xrender_color, xerror::trap_errors, Draw, Error, Rect, Result};
+use std::os::raw::{c_int, c_ulong};
+use x11::{
+    xlib::{
+        DirectColor, Drawable, FillTiled, LSBFirst, TrueColor, Visual, XCreateImage, XCreatePixmap,
+        XDestroyImage, XFillRectangle, XFreePixmap, XGetImage, XImageByteOrder, XPutImage,
+        XSetFillStyle, XSetTSOrigin, XSetTile, ZPixmap,
+    },
+    xrender::{
+        PictOpSrc, XRenderComposite, XRenderCreatePicture, XRenderFillRectangle,
+        XRenderFindVisualFormat, XRenderFreePicture, XRenderSetPictureFilter,
+        XRenderSetPictureTransform, XTransform,
+    },
+};
+
+impl Draw {
+    /// Draw tightly packed RGBA pixel data (4 bytes per pixel, row major) with its top left
+    /// corner at (x, y). The alpha channel is ignored.
+    pub fn draw_image(&mut self, data: &[u8], w: u32, h: u32, x: i32, y: i32) -> Result<()> {
+        self.assert_thread();
+        check_image_len(data, w, h)?;
+        let (x, y) = self.translate_point((x, y));
+        self.ensure_fits(Rect { x, y, w, h })?;
+
+        unsafe { self.put_image(self.drawable, data, w, h, (x, y)) }
+    }
+
+    /// Read back the full contents of the drawable as tightly packed RGBA pixel data (in the
+    /// same format as [Draw::draw_image]) along with its width and height.
+    ///
+    /// Visuals without an alpha channel report every pixel as fully opaque.
+    pub fn snapshot(&self) -> Result<(Vec<u8>, u32, u32)> {
+        self.assert_thread();
+        let (w, h) = (self.w, self.h);
+
+        unsafe {
+            // This fails with BadMatch if the drawable is a window that is not fully visible
+            // or a pixmap provided by the caller that is smaller than our size
+            let (img, err) = trap_errors(self.dpy, || {
+                XGetImage(self.dpy, self.drawable, 0, 0, w, h, !0, ZPixmap)
+            });
+            if let Some(e) = err {
+                log_error!("reading back the drawable failed: {}", Error::X(e));
+            }
+            if img.is_null() || err.is_some() {
+                if !img.is_null() {
+                    XDestroyImage(img);
+                }
+                return Err(Error::UnableToReadDrawable { w, h });
+            }
+
+            let fmt = match PixelFormat::try_new(
+                &*self.vis.visual,
+                self.vis.depth,
+                (*img).bits_per_pixel,
+                (*img).byte_order,
+            ) {
+                Ok(fmt) => fmt,
+                Err(e) => {
+                    XDestroyImage(img);
+                    return Err(e);
+                }
+            };
+
+            let stride = (*img).bytes_per_line as usize;
+            let buf = std::slice::from_raw_parts((*img).data as *const u8, stride * h as usize);
+            let rgba = fmt.unpack(buf, w as usize, h as usize, stride);
+            XDestroyImage(img);
+
+            Ok((rgba, w, h))
+        }
+    }
+
+    /// Decode the PNG file at `path` and draw it with its top left corner at (x, y).
+    #[cfg(feature = "image")]
+    pub fn draw_png(&mut self, path: &str, x: i32, y: i32) -> Result<()> {
+        let img = ::image::open(path)
+            .map_err(|e| Error::ImageDecode(e.to_string()))?
+            .into_rgba8();
+        let (w, h) = img.dimensions();
+
+        self.draw_image(img.as_raw(), w, h, x, y)
+    }
+
+    /// Draw RGBA pixel data (in the same format as [Draw::draw_image]) of size src_w x src_h
+    /// scaled to fill `dst` using bilinear filtering.
+    pub fn draw_image_scaled(
+        &mut self,
+        data: &[u8],
+        src_w: u32,
+        src_h: u32,
+        dst: Rect,
+    ) -> Result<()> {
+        self.assert_thread();
+        check_image_len(data, src_w, src_h)?;
+        let dst = self.translate_rect(dst);
+        self.ensure_fits(dst)?;
+        if dst.w == 0 || dst.h == 0 {
+            return Ok(());
+        }
+
+        unsafe {
+            let visual = self.vis.visual;
+            let fmt = XRenderFindVisualFormat(self.dpy, visual);
+            if fmt.is_null() {
+                return Err(Error::UnsupportedVisual((*visual).class));
+            }
+
+            let depth = self.vis.depth as u32;
+            let pixmap = XCreatePixmap(self.dpy, self.root, src_w, src_h, depth);
+            if let Err(e) = self.put_scratch_image(pixmap, data, src_w, src_h) {
+                XFreePixmap(self.dpy, pixmap);
+                return Err(e);
+            }
+
+            let src = XRenderCreatePicture(self.dpy, pixmap, fmt, 0, std::ptr::null());
+            let dst_pic = XRenderCreatePicture(self.dpy, self.drawable, fmt, 0, std::ptr::null());
+            self.clip_picture(dst_pic);
+
+            // The transform maps destination coordinates back into the source image
+            let fixed = |f: f64| (f * 65536.0) as i32;
+            let mut transform = XTransform {
+                matrix: [
+                    [fixed(src_w as f64 / dst.w as f64), 0, 0],
+                    [0, fixed(src_h as f64 / dst.h as f64), 0],
+                    [0, 0, fixed(1.0)],
+                ],
+            };
+            XRenderSetPictureTransform(self.dpy, src, &mut transform);
+            XRenderSetPictureFilter(self.dpy, src, c"bilinear".as_ptr(), std::ptr::null_mut(), 0);
+
+            XRenderComposite(
+                self.dpy, PictOpSrc, src, 0, dst_pic, 0, 0, 0, 0, dst.x, dst.y, dst.w, dst.h,
+            );
+
+            XRenderFreePicture(self.dpy, src);
+            XRenderFreePicture(self.dpy, dst_pic);
+            XFreePixmap(self.dpy, pixmap);
+        }
+
+        Ok(())
+    }
+
+    /// Use the given RGBA pixel data (in the same format as [Draw::draw_image]) as the
+    /// background for [Draw::clear] and the background of text, tiling it across the
+    /// drawable in place of the background color of the active colorscheme.
+    pub fn set_background_image(&mut self, data: &[u8], w: u32, h: u32) -> Result<()> {
+        self.assert_thread();
+        check_image_len(data, w, h)?;
+        self.clear_background_image();
+
+        unsafe {
+            let depth = self.vis.depth as u32;
+            let pixmap = XCreatePixmap(self.dpy, self.root, w, h, depth);
+            if let Err(e) = self.put_scratch_image(pixmap, data, w, h) {
+                XFreePixmap(self.dpy, pixmap);
+                return Err(e);
+            }
+
+            self.bg_image = pixmap;
+        }
+
+        Ok(())
+    }
+
+    /// Go back to using the background color of the active colorscheme.
+    pub fn clear_background_image(&mut self) {
+        if self.bg_image != 0 {
+            unsafe { XFreePixmap(self.dpy, self.bg_image) };
+            self.bg_image = 0;
+        }
+    }
+
+    /// Leave the background behind text drawn without `invert` fully transparent rather than
+    /// filling it with the background of the active colorscheme or background image, so that
+    /// only the glyphs themselves are opaque.
+    ///
+    /// This is only useful with a 32-bit visual (see [Draw::new_argb]) and a compositor
+    /// running: otherwise the background is drawn as black.
+    pub fn set_transparent_text_background(&mut self, transparent: bool) {
+        self.transparent_text_bg = transparent;
+    }
+
+    // Set a rect (which has already had the current translation applied) to fully
+    // transparent, replacing rather than blending with what is already there
+    pub(crate) fn fill_transparent(&mut self, r: Rect) {
+        let Some(Rect { x, y, w, h }) = r.clamped_to(self.w, self.h) else {
+            return;
+        };
+
+        unsafe {
+            let fmt = XRenderFindVisualFormat(self.dpy, self.vis.visual);
+            if fmt.is_null() {
+                return self.fill_pixel(r, 0);
+            }
+
+            let pic = XRenderCreatePicture(self.dpy, self.drawable, fmt, 0, std::ptr::null());
+            self.clip_picture(pic);
+            let clear = xrender_color([0; 4]);
+            XRenderFillRectangle(self.dpy, PictOpSrc, pic, &clear, x, y, w, h);
+            XRenderFreePicture(self.dpy, pic);
+        }
+    }
+
+    // Fill a rect (which has already had the current translation applied) with the
+    // background image if there is one or the scheme background otherwise.
+    pub(crate) fn fill_background(&mut self, r: Rect) {
+        if self.bg_image == 0 {
+            return self.fill(r, true);
+        }
+
+        let Some(Rect { x, y, w, h }) = r.clamped_to(self.w, self.h) else {
+            return;
+        };
+        unsafe {
+            XSetTile(self.dpy, self.gc, self.bg_image);
+            XSetTSOrigin(self.dpy, self.gc, 0, 0);
+            XSetFillStyle(self.dpy, self.gc, FillTiled);
+            XFillRectangle(self.dpy, self.drawable, self.gc, x, y, w, h);
+        }
+        self.restore_fill_style();
+    }
+
+    // Put an image into a pixmap other than the drawable, ignoring the clip
+    unsafe fn put_scratch_image(
+        &self,
+        target: Drawable,
+        data: &[u8],
+        w: u32,
+        h: u32,
+    ) -> Result<()> {
+        self.suspend_clip();
+        let res = self.put_image(target, data, w, h, (0, 0));
+        self.resume_clip();
+
+        res
+    }
+
+    unsafe fn put_image(
+        &self,
+        target: Drawable,
+        data: &[u8],
+        w: u32,
+        h: u32,
+        (x, y): (i32, i32),
+    ) -> Result<()> {
+        let visual = self.vis.visual;
+        let depth = self.vis.depth as u32;
+
+        // Let Xlib work out bits_per_pixel and bytes_per_line for this depth before we
+        // allocate the buffer that will back the image.
+        let img = XCreateImage(
+            self.dpy,
+            visual,
+            depth,
+            ZPixmap,
+            0,
+            std::ptr::null_mut(),
+            w,
+            h,
+            32,
+            0,
+        );
+        if img.is_null() {
+            return Err(Error::UnableToCreateImage);
+        }
+
+        let fmt = match PixelFormat::try_new(
+            &*visual,
+            self.vis.depth,
+            (*img).bits_per_pixel,
+            XImageByteOrder(self.dpy),
+        ) {
+            Ok(fmt) => fmt,
+            Err(e) => {
+                XDestroyImage(img);
+                return Err(e);
+            }
+        };
+
+        let stride = (*img).bytes_per_line as usize;
+        let mut buf = fmt.pack(data, w as usize, h as usize, stride);
+        (*img).data = buf.as_mut_ptr() as *mut _;
+
+        XPutImage(self.dpy, target, self.gc, img, 0, 0, x, y, w, h);
+
+        // XDestroyImage frees the data pointer it holds but buf is owned by us
+        (*img).data = std::ptr::null_mut();
+        XDestroyImage(img);
+
+        Ok(())
+    }
+}
+
+fn check_image_len(data: &[u8], w: u32, h: u32) -> Result<()> {
+    let expected = w as usize * h as usize * 4;
+    if data.len() != expected {
+        return Err(Error::InvalidImageData {
+            expected,
+            got: data.len(),
+        });
+    }
+
+    Ok(())
+}
+
+// Where a single 8-bit color channel lives within a pixel value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Channel {
+    shift: u32,
+    bits: u32,
+}
+
+impl Channel {
+    fn from_mask(mask: c_ulong) -> Self {
+        Self {
+            shift: mask.trailing_zeros(),
+            bits: mask.count_ones(),
+        }
+    }
+
+    fn encode(&self, c: u8) -> u64 {
+        if self.bits == 0 {
+            return 0;
+        }
+
+        let c = c as u64;
+        let v = if self.bits <= 8 {
+            c >> (8 - self.bits)
+        } else {
+            c << (self.bits - 8)
+        };
+
+        v << self.shift
+    }
+
+    fn decode(&self, p: u64) -> u8 {
+        if self.bits == 0 {
+            return 0;
+        }
+
+        let v = (p >> self.shift) & ((1 << self.bits) - 1);
+        if self.bits >= 8 {
+            (v >> (self.bits - 8)) as u8
+        } else {
+            (v * 0xff / ((1 << self.bits) - 1)) as u8
+        }
+    }
+}
+
+// How RGB values need to be laid out in memory for a given visual and server byte order.
+//
+// Servers are free to pick their own byte order and visuals their own channel masks so we
+// can't assume little-endian 32-bit BGRA. The bitmap_bit_order of the server only applies
+// to depth 1 images so it is not relevant here.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct PixelFormat {
+    red: Channel,
+    green: Channel,
+    blue: Channel,
+    alpha: Channel,
+    bytes_per_pixel: usize,
+    msb_first: bool,
+}
+
+impl PixelFormat {
+    pub(crate) fn try_new(
+        visual: &Visual,
+        depth: c_int,
+        bits_per_pixel: c_int,
+        byte_order: c_int,
+    ) -> Result<Self> {
+        if visual.class != TrueColor && visual.class != DirectColor {
+            return Err(Error::UnsupportedVisual(visual.class));
+        }
+
+        if ![16, 24, 32].contains(&bits_per_pixel) {
+            return Err(Error::UnsupportedPixelFormat(bits_per_pixel));
+        }
+
+        // Any bits within the depth that aren't used for color hold alpha, which is always
+        // set to fully opaque as the alpha channel of input data is ignored
+        let rgb_mask = visual.red_mask | visual.green_mask | visual.blue_mask;
+        let depth_mask = if depth >= 64 {
+            c_ulong::MAX
+        } else {
+            (1 << depth) - 1
+        };
+
+        Ok(Self {
+            red: Channel::from_mask(visual.red_mask),
+            green: Channel::from_mask(visual.green_mask),
+            blue: Channel::from_mask(visual.blue_mask),
+            alpha: Channel::from_mask(depth_mask & !rgb_mask),
+            bytes_per_pixel: bits_per_pixel as usize / 8,
+            msb_first: byte_order != LSBFirst,
+        })
+    }
+
+    fn pixel(&self, r: u8, g: u8, b: u8) -> u64 {
+        self.red.encode(r) | self.green.encode(g) | self.blue.encode(b) | self.alpha.encode(0xff)
+    }
+
+    // Pack RGBA input into rows of `stride` bytes in the server's pixel format
+    pub(crate) fn pack(&self, rgba: &[u8], w: usize, h: usize, stride: usize) -> Vec<u8> {
+        let bpp = self.bytes_per_pixel;
+        let mut buf = vec![0; stride * h];
+
+        for (row, src) in rgba.chunks_exact(w * 4).enumerate() {
+            let dst = &mut buf[row * stride..row * stride + w * bpp];
+            for (px, out) in src.chunks_exact(4).zip(dst.chunks_exact_mut(bpp)) {
+                let p = self.pixel(px[0], px[1], px[2]);
+                for (i, byte) in out.iter_mut().enumerate() {
+                    let shift = if self.msb_first { bpp - 1 - i } else { i } * 8;
+                    *byte = (p >> shift) as u8;
+                }
+            }
+        }
+
+        buf
+    }
+
+    // Unpack rows of `stride` bytes in the server's pixel format into RGBA
+    pub(crate) fn unpack(&self, buf: &[u8], w: usize, h: usize, stride: usize) -> Vec<u8> {
+        let bpp = self.bytes_per_pixel;
+        let mut rgba = Vec::with_capacity(w * h * 4);
+
+        for src in buf.chunks_exact(stride).take(h) {
+            for px in src[..w * bpp].chunks_exact(bpp) {
+                let p = px.iter().enumerate().fold(0u64, |p, (i, &byte)| {
+                    let shift = if self.msb_first { bpp - 1 - i } else { i } * 8;
+                    p | (byte as u64) << shift
+                });
+
+                let a = if self.alpha.bits == 0 {
+                    0xff
+                } else {
+                    self.alpha.decode(p)
+                };
+                rgba.extend_from_slice(&[
+                    self.red.decode(p),
+                    self.green.decode(p),
+                    self.blue.decode(p),
+                    a,
+                ]);
+            }
+        }
+
+        rgba
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use x11::xlib::{MSBFirst, PseudoColor};
+
+    fn visual(class: c_int, red: c_ulong, green: c_ulong, blue: c_ulong) -> Visual {
+        Visual {
+            ext_data: std::ptr::null_mut(),
+            visualid: 0,
+            class,
+            red_mask: red,
+            green_mask: green,
+            blue_mask: blue,
+            bits_per_rgb: 8,
+            map_entries: 256,
+        }
+    }
+
+    fn format(vis: &Visual, depth: c_int, bpp: c_int, byte_order: c_int) -> PixelFormat {
+        PixelFormat::try_new(vis, depth, bpp, byte_order).unwrap()
+    }
+
+    const RGB: [u8; 8] = [0x12, 0x34, 0x56, 0x00, 0xff, 0x80, 0x01, 0x00];
+
+    #[test]
+    fn depth_24_lsb_first() {
+        let vis = visual(TrueColor, 0xff0000, 0xff00, 0xff);
+        let fmt = format(&vis, 24, 32, LSBFirst);
+        let packed = fmt.pack(&RGB, 2, 1, 8);
+
+        assert_eq!(packed, [0x56, 0x34, 0x12, 0x00, 0x01, 0x80, 0xff, 0x00]);
+        // There is no alpha channel so everything reads back as opaque
+        assert_eq!(
+            fmt.unpack(&packed, 2, 1, 8),
+            [0x12, 0x34, 0x56, 0xff, 0xff, 0x80, 0x01, 0xff]
+        );
+    }
+
+    #[test]
+    fn depth_24_msb_first() {
+        let vis = visual(TrueColor, 0xff0000, 0xff00, 0xff);
+        let fmt = format(&vis, 24, 32, MSBFirst);
+        let packed = fmt.pack(&RGB, 2, 1, 8);
+
+        assert_eq!(packed, [0x00, 0x12, 0x34, 0x56, 0x00, 0xff, 0x80, 0x01]);
+        assert_eq!(
+            fmt.unpack(&packed, 2, 1, 8),
+            [0x12, 0x34, 0x56, 0xff, 0xff, 0x80, 0x01, 0xff]
+        );
+    }
+
+    #[test]
+    fn depth_32_sets_alpha_opaque() {
+        let vis = visual(TrueColor, 0xff0000, 0xff00, 0xff);
+        for (order, expected) in [
+            (LSBFirst, [0x56, 0x34, 0x12, 0xff]),
+            (MSBFirst, [0xff, 0x12, 0x34, 0x56]),
+        ] {
+            let fmt = format(&vis, 32, 32, order);
+            let packed = fmt.pack(&[0x12, 0x34, 0x56, 0x00], 1, 1, 4);
+
+            assert_eq!(packed, expected);
+            assert_eq!(fmt.unpack(&packed, 1, 1, 4), [0x12, 0x34, 0x56, 0xff]);
+        }
+    }
+
+    #[test]
+    fn depth_32_reads_back_alpha() {
+        let vis = visual(TrueColor, 0xff0000, 0xff00, 0xff);
+        let fmt = format(&vis, 32, 32, LSBFirst);
+
+        assert_eq!(
+            fmt.unpack(&[0x56, 0x34, 0x12, 0x80], 1, 1, 4),
+            [0x12, 0x34, 0x56, 0x80]
+        );
+    }
+
+    #[test]
+    fn rgb_565() {
+        let vis = visual(TrueColor, 0xf800, 0x07e0, 0x1f);
+        for (order, expected) in [(LSBFirst, [0x1f, 0xf8]), (MSBFirst, [0xf8, 0x1f])] {
+            let fmt = format(&vis, 16, 16, order);
+            let packed = fmt.pack(&[0xff, 0x00, 0xff, 0x00], 1, 1, 2);
+
+            assert_eq!(packed, expected);
+            assert_eq!(fmt.unpack(&packed, 1, 1, 2), [0xff, 0x00, 0xff, 0xff]);
+        }
+    }
+
+    #[test]
+    fn rgb_565_round_trip_is_stable() {
+        let vis = visual(TrueColor, 0xf800, 0x07e0, 0x1f);
+        let fmt = format(&vis, 16, 16, LSBFirst);
+        let packed = fmt.pack(&RGB, 2, 1, 4);
+        let unpacked = fmt.unpack(&packed, 2, 1, 4);
+
+        // Low bits are lost going down to 5 or 6 bits per channel but packing what we read
+        // back must give the same pixels again
+        assert_eq!(fmt.pack(&unpacked, 2, 1, 4), packed);
+    }
+
+    #[test]
+    fn round_trip_with_row_padding() {
+        let vis = visual(TrueColor, 0xff0000, 0xff00, 0xff);
+        let rgba: Vec<u8> = (0..2 * 3 * 4)
+            .map(|i| if i % 4 == 3 { 0xff } else { i as u8 * 10 })
+            .collect();
+
+        for order in [LSBFirst, MSBFirst] {
+            let fmt = format(&vis, 24, 32, order);
+            // 3 pixels of 4 bytes padded out to 16 bytes per row
+            let packed = fmt.pack(&rgba, 3, 2, 16);
+
+            assert_eq!(packed.len(), 32);
+            assert_eq!(fmt.unpack(&packed, 3, 2, 16), rgba);
+        }
+    }
+
+    #[test]
+    fn unsupported_formats_are_rejected() {
+        let pseudo = visual(PseudoColor, 0, 0, 0);
+        let true_color = visual(TrueColor, 0xff0000, 0xff00, 0xff);
+
+        assert!(matches!(
+            PixelFormat::try_new(&pseudo, 8, 8, LSBFirst),
+            Err(Error::UnsupportedVisual(class)) if class == PseudoColor
+        ));
+        assert!(matches!(
+            PixelFormat::try_new(&true_color, 8, 8, LSBFirst),
+            Err(Error::UnsupportedPixelFormat(8))
+        ));
+    }
+}
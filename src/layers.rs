@@ -0,0 +1,150 @@
+//! Additional pixmaps that can be drawn into independently and composited together
+//!
+//! Rather than threading a target through every draw method, selecting a layer swaps its
+//! pixmap with the one held by the [Draw] itself. While a layer is selected its slot in
+//! `layers` holds the main pixmap so that swapping back restores it.
+use crate::{xerror::trap_errors, Draw, Error, Rect, Result};
+use std::mem;
+use x11::xlib::{Drawable, Window, XCopyArea, XCreatePixmap, XFreePixmap};
+
+/// An opaque handle to a layer created using [Draw::create_layer].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LayerId(usize);
+
+#[derive(Debug)]
+pub(crate) struct Layer {
+    drawable: Drawable,
+    w: u32,
+    h: u32,
+    owned: bool,
+}
+
+impl Draw {
+    /// Create a new `w` x `h` layer that can be drawn into by selecting it with
+    /// [Draw::set_target_layer]. Layers start out filled with a pixel value of zero, which is
+    /// black (or fully transparent when using [Draw::new_argb]).
+    pub fn create_layer(&mut self, w: u32, h: u32) -> LayerId {
+        self.assert_thread();
+        let drawable = unsafe { XCreatePixmap(self.dpy, self.root, w, h, self.vis.depth as u32) };
+        log_debug!("created {w}x{h} layer pixmap {drawable}");
+        self.layers.push(Layer {
+            drawable,
+            w,
+            h,
+            owned: true,
+        });
+        let id = LayerId(self.layers.len() - 1);
+
+        // Swapping the new layer in and back out again leaves the current target in place
+        self.swap_layer(id.0);
+        self.suspend_clip();
+        self.fill_pixel(Rect::from_size(w, h), 0);
+        self.resume_clip();
+        self.swap_layer(id.0);
+
+        id
+    }
+
+    /// Direct all subsequent draw calls (and flushes) to the given layer, or back to the main
+    /// drawable if `layer` is `None`. Resizing while a layer is selected resizes that layer.
+    pub fn set_target_layer(&mut self, layer: Option<LayerId>) -> Result<()> {
+        self.assert_thread();
+        if let Some(LayerId(i)) = layer {
+            if i >= self.layers.len() {
+                return Err(Error::UnknownLayer(i));
+            }
+        }
+
+        self.select_main_drawable();
+        if let Some(LayerId(i)) = layer {
+            self.swap_layer(i);
+            self.active_layer = Some(i);
+        }
+
+        Ok(())
+    }
+
+    /// Copy the region `r` of the main drawable followed by each layer in the order they were
+    /// created to the same position in the given window, syncing once at the end.
+    ///
+    /// Layers are opaque, so a layer covers anything beneath it wherever it overlaps `r`.
+    pub fn composite_layers_to(&mut self, win: u32, r: Rect) -> Result<()> {
+        self.assert_thread();
+        let mut sources = vec![(self.drawable, self.w, self.h)];
+        sources.extend(self.layers.iter().map(|l| (l.drawable, l.w, l.h)));
+        // The main pixmap and the active layer have swapped places
+        if let Some(i) = self.active_layer {
+            sources.swap(0, i + 1);
+        }
+
+        self.suspend_clip();
+        let (_, err) = unsafe {
+            trap_errors(self.dpy, || {
+                for (drawable, w, h) in sources {
+                    let (x, y) = (r.x.max(0), r.y.max(0));
+                    let right = (r.x as i64 + r.w as i64).min(w as i64);
+                    let bottom = (r.y as i64 + r.h as i64).min(h as i64);
+                    if right <= x as i64 || bottom <= y as i64 {
+                        continue;
+                    }
+
+                    let (cw, ch) = ((right - x as i64) as u32, (bottom - y as i64) as u32);
+                    XCopyArea(
+                        self.dpy,
+                        drawable,
+                        win as Window,
+                        self.gc,
+                        x,
+                        y,
+                        cw,
+                        ch,
+                        x,
+                        y,
+                    );
+                }
+            })
+        };
+        self.resume_clip();
+
+        match err {
+            Some(e) => Err(Error::X(e)),
+            None => Ok(()),
+        }
+    }
+
+    fn swap_layer(&mut self, i: usize) {
+        let layer = &mut self.layers[i];
+        mem::swap(&mut self.drawable, &mut layer.drawable);
+        mem::swap(&mut self.w, &mut layer.w);
+        mem::swap(&mut self.h, &mut layer.h);
+        mem::swap(&mut self.owns_drawable, &mut layer.owned);
+    }
+
+    pub(crate) fn select_main_drawable(&mut self) {
+        if let Some(i) = self.active_layer.take() {
+            self.swap_layer(i);
+        }
+    }
+
+    // Go back to the main drawable and free the pixmaps of all layers, leaving the layers
+    // themselves in place so that they can be recreated
+    pub(crate) fn free_layers(&mut self) {
+        self.select_main_drawable();
+        for layer in self.layers.iter() {
+            if layer.owned {
+                unsafe { XFreePixmap(self.dpy, layer.drawable) };
+            }
+        }
+    }
+
+    // Replace the pixmaps of all layers with new ones of the same size, for use after the
+    // connection they were created on has been lost. Their contents are not preserved.
+    pub(crate) fn recreate_layers(&mut self) {
+        for layer in self.layers.iter_mut() {
+            layer.drawable = unsafe {
+                XCreatePixmap(self.dpy, self.root, layer.w, layer.h, self.vis.depth as u32)
+            };
+            layer.owned = true;
+        }
+    }
+}
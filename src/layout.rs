@@ -0,0 +1,299 @@
+//! Laying out text as runs of glyphs that share a font
+//!
+//! Both measuring and drawing text go through [Draw::layout_run] so that the two can never
+//! disagree about where each glyph ends up. Anything that changes the advance of glyphs
+//! needs to be handled here rather than in the individual draw methods.
+use crate::{
+    fontset::{latin1, FontMatch},
+    Draw, Rect, Result,
+};
+use std::ffi::CString;
+use x11::{
+    xft::{XftColor, XftDraw, XftDrawDrawable, XftDrawStringUtf8},
+    xlib::{XDrawString, XSetFont, XSetForeground},
+};
+
+// A contiguous run of text rendered using a single font, positioned relative to the start
+// of the line it belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct PositionedGlyphRun<'a> {
+    pub(crate) txt: &'a str,
+    pub(crate) fm: FontMatch,
+    pub(crate) x: i32,
+    pub(crate) w: i32,
+    pub(crate) h: i32,
+}
+
+/// A contiguous substring of laid out text that is rendered using a single font.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GlyphRun<'a> {
+    /// The text making up this run
+    pub txt: &'a str,
+    /// The font used to render this run: 0 is the primary font and anything else is a
+    /// fallback font that was loaded to cover characters missing from the primary
+    pub font_index: usize,
+    /// The x coordinate of the start of this run
+    pub x: i32,
+    /// The advance width of this run
+    pub w: i32,
+}
+
+/// The measured size of a string along with the bearings of its ink, as returned by
+/// [Draw::text_extent_full].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Extent {
+    /// The advance width of the text: where the next string should start
+    pub width: i32,
+    /// The height of the tallest font used to render the text
+    pub height: i32,
+    /// The distance from the left edge of the ink of the first glyph back to the origin.
+    /// This is negative when the ink starts to the right of the origin and positive when
+    /// it overhangs to the left.
+    pub x_bearing: i16,
+    /// The distance from the top of the tallest glyph down to the baseline
+    pub y_bearing: i16,
+}
+
+impl Draw {
+    /// The [Extent] of `txt` as it would be rendered by [Draw::draw_text], including the
+    /// bearings that are needed to position text whose ink overhangs its advance width
+    /// (such as italics).
+    pub fn text_extent_full(&mut self, txt: &str) -> Result<Extent> {
+        self.assert_thread();
+        if txt.is_empty() {
+            return Ok(Extent::default());
+        }
+
+        let runs = self.layout_run(txt)?;
+        let (width, height) = runs_extent(&runs);
+        let mut ext = Extent {
+            width,
+            height,
+            ..Default::default()
+        };
+
+        for (i, run) in runs.iter().enumerate() {
+            let info = self.fs.fnt(run.fm).glyph_info(self.dpy, run.txt)?;
+            if i == 0 {
+                ext.x_bearing = info.x;
+            }
+            ext.y_bearing = ext.y_bearing.max(info.y);
+        }
+
+        Ok(ext)
+    }
+
+    /// Lay out `txt` as it would be rendered by [Draw::draw_text] starting at `r.x` without
+    /// drawing anything. This can be used for hit testing or for drawing individual runs
+    /// selectively.
+    pub fn layout<'a>(&mut self, txt: &'a str, r: Rect) -> Result<Vec<GlyphRun<'a>>> {
+        self.assert_thread();
+        let runs = self.layout_run(txt)?;
+
+        Ok(runs
+            .into_iter()
+            .map(|run| GlyphRun {
+                txt: run.txt,
+                font_index: run.fm.index(),
+                x: r.x + run.x,
+                w: run.w,
+            })
+            .collect())
+    }
+
+    /// Add `px` pixels of space between each character of text drawn or measured from now
+    /// on. Negative values move characters closer together. Defaults to 0.
+    pub fn set_letter_spacing(&mut self, px: i32) {
+        self.letter_spacing = px;
+    }
+
+    /// Set the distance between tab stops as a number of spaces in the primary font. Tabs
+    /// aren't drawn: they move the text that follows them on to the next tab stop, measured
+    /// from the start of the text. Defaults to 8.
+    pub fn set_tab_width(&mut self, spaces: u32) {
+        self.tab_width = spaces;
+    }
+
+    pub(crate) fn layout_run<'a>(&mut self, txt: &'a str) -> Result<Vec<PositionedGlyphRun<'a>>> {
+        // Checked once up front so that the runs can be measured without converting each one
+        CString::new(txt)?;
+        let tab_stop = self.tab_stop(txt)?;
+        let chunks = self.fs.per_font_chunks(txt);
+        let mut runs = Vec::new();
+        self.position_chunks(&chunks, tab_stop, &mut runs);
+
+        Ok(runs)
+    }
+
+    // Lay out txt using the given font for every character, ignoring per-character matching
+    pub(crate) fn layout_run_in_font<'a>(
+        &mut self,
+        txt: &'a str,
+        fm: FontMatch,
+    ) -> Result<Vec<PositionedGlyphRun<'a>>> {
+        CString::new(txt)?;
+        let tab_stop = self.tab_stop(txt)?;
+        let mut runs = Vec::new();
+        self.position_chunks(&[(txt, fm)], tab_stop, &mut runs);
+
+        Ok(runs)
+    }
+
+    // The distance between tab stops in pixels, only measured if txt actually contains tabs
+    pub(crate) fn tab_stop(&mut self, txt: &str) -> Result<i32> {
+        if !txt.contains('\t') {
+            return Ok(0);
+        }
+
+        Ok(self.fs.space_width()? * self.tab_width as i32)
+    }
+
+    // Append the runs for a line made up of the given chunks, none of which may contain nul
+    // bytes. Each tab is given a run of its own so that it can be skipped when drawing, and
+    // with letter spacing every character is a run of its own so that it can be positioned
+    // individually.
+    pub(crate) fn position_chunks<'a>(
+        &self,
+        chunks: &[(&'a str, FontMatch)],
+        tab_stop: i32,
+        runs: &mut Vec<PositionedGlyphRun<'a>>,
+    ) {
+        let mut x = 0;
+
+        for &(chunk, fm) in chunks {
+            let fnt = self.fs.fnt(fm);
+            let run = |txt, x, w| PositionedGlyphRun {
+                txt,
+                fm,
+                x,
+                w,
+                h: fnt.h,
+            };
+
+            for piece in split_tabs(chunk) {
+                if piece == "\t" {
+                    let w = next_tab_stop(x, tab_stop) - x;
+                    runs.push(run(piece, x, w));
+                    x += w;
+                } else if self.letter_spacing == 0 {
+                    let w = fnt.advance(self.dpy, piece);
+                    runs.push(run(piece, x, w));
+                    x += w;
+                } else {
+                    for (i, c) in piece.char_indices() {
+                        let glyph = &piece[i..i + c.len_utf8()];
+                        let w = fnt.advance(self.dpy, glyph);
+                        runs.push(run(glyph, x, w));
+                        x += w + self.letter_spacing;
+                    }
+                }
+            }
+        }
+    }
+
+    // The advance of the first `end` bytes of a run returned by layout_run
+    pub(crate) fn run_prefix_width(&self, run: &PositionedGlyphRun<'_>, end: usize) -> i32 {
+        if end >= run.txt.len() {
+            return run.w;
+        }
+
+        self.fs.fnt(run.fm).advance(self.dpy, &run.txt[..end])
+    }
+
+    // Render runs with the start of the line at x, vertically centering each run within a
+    // line of height h that starts at y.
+    pub(crate) unsafe fn draw_runs(
+        &self,
+        d: *mut XftDraw,
+        color: *const XftColor,
+        runs: &[PositionedGlyphRun<'_>],
+        (x, y): (i32, i32),
+        h: u32,
+    ) -> Result<()> {
+        for run in runs {
+            if run.txt == "\t" {
+                continue;
+            }
+
+            let fnt = self.fs.fnt(run.fm);
+            let run_y = y + (h as i32 - run.h) / 2 + fnt.ascent();
+
+            if fnt.is_core() {
+                let bytes = latin1(run.txt);
+                XSetFont(self.dpy, self.gc, (*fnt.core).fid);
+                XSetForeground(self.dpy, self.gc, (*color).pixel);
+                XDrawString(
+                    self.dpy,
+                    XftDrawDrawable(d),
+                    self.gc,
+                    x + run.x,
+                    run_y,
+                    bytes.as_ptr() as *const _,
+                    bytes.len() as i32,
+                );
+                continue;
+            }
+
+            let c_str = CString::new(run.txt)?;
+            XftDrawStringUtf8(
+                d,
+                color,
+                fnt.xfont,
+                x + run.x,
+                run_y,
+                c_str.as_ptr() as *mut _,
+                c_str.as_bytes().len() as i32,
+            );
+        }
+
+        Ok(())
+    }
+}
+
+// The total width and maximum height of a laid out line
+pub(crate) fn runs_extent(runs: &[PositionedGlyphRun<'_>]) -> (i32, i32) {
+    let w = runs.last().map(|r| r.x + r.w).unwrap_or(0);
+    let h = runs.iter().map(|r| r.h).max().unwrap_or(0);
+
+    (w, h)
+}
+
+// Split txt into the text between tabs and the tabs themselves, dropping empty pieces
+fn split_tabs(txt: &str) -> impl Iterator<Item = &str> {
+    txt.split_inclusive('\t')
+        .flat_map(|s| match s.strip_suffix('\t') {
+            Some(before) => [before, &s[before.len()..]],
+            None => [s, ""],
+        })
+        .filter(|s| !s.is_empty())
+}
+
+// The position of the first tab stop after x, where stops are every `stop` pixels
+fn next_tab_stop(x: i32, stop: i32) -> i32 {
+    if stop <= 0 {
+        return x;
+    }
+
+    (x.div_euclid(stop) + 1) * stop
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_tabs_keeps_each_tab_as_a_piece() {
+        let pieces: Vec<&str> = split_tabs("\ta\t\tbc\t").collect();
+
+        assert_eq!(pieces, ["\t", "a", "\t", "\t", "bc", "\t"]);
+    }
+
+    #[test]
+    fn next_tab_stop_always_moves_forward() {
+        assert_eq!(next_tab_stop(0, 40), 40);
+        assert_eq!(next_tab_stop(39, 40), 40);
+        assert_eq!(next_tab_stop(40, 40), 80);
+        assert_eq!(next_tab_stop(-10, 40), 0);
+        assert_eq!(next_tab_stop(17, 0), 17);
+    }
+}
@@ -1,45 +1,182 @@
 //! About the smallest drawing API you could ask for
+//!
+//! X errors raised by operations that already need to round trip to the server (such as
+//! [Draw::flush_to]) are trapped using a scoped error handler rather than being left to the
+//! default Xlib handler which will exit the process. See [XErrorInfo] for details.
 use std::{
-    alloc::{alloc, dealloc, handle_alloc_error, Layout},
+    collections::HashMap,
     ffi::{CString, NulError},
 };
 use x11::{
-    xft::{XftColor, XftColorAllocName, XftDrawCreate, XftDrawStringUtf8},
+    xft::{XftDrawCreate, XftDrawDestroy},
     xlib::{
-        CapButt, Display, Drawable, False, JoinMiter, LineSolid, Window, XCopyArea, XCreateGC,
-        XCreatePixmap, XDefaultColormap, XDefaultDepth, XDefaultVisual, XDrawRectangle,
-        XFillRectangle, XFreeGC, XFreePixmap, XOpenDisplay, XSetForeground, XSetLineAttributes,
-        XSync, GC,
+        BadDrawable, BadWindow, Colormap, Display, Drawable, False, Pixmap, Region, True, Visual,
+        Window, XCloseDisplay, XCopyArea, XCreateGC, XCreatePixmap, XDrawLine, XDrawRectangle,
+        XFillRectangle, XFillRectangles, XFreeGC, XFreePixmap, XOpenDisplay, XRectangle,
+        XSetForeground, XSync, GC,
     },
 };
 
+#[macro_use]
+mod logging;
+
+#[cfg(feature = "bar")]
+mod bar;
+mod clip;
+mod color;
+mod connection;
+mod dither;
+mod draw_list;
+mod encoding;
+mod events;
 mod fontset;
+mod frame;
+mod image;
+mod layers;
+mod layout;
+mod metrics;
+mod multiline;
+mod regions;
+mod rich_text;
+mod rotated;
+#[cfg(feature = "randr")]
+mod screens;
+mod shapes;
+mod style;
+mod visual;
+mod window;
+mod xerror;
+
+#[cfg(feature = "bar")]
+pub use bar::{Bar, Module};
+use color::Color;
+pub use connection::ServerInfo;
+pub use draw_list::{DrawCmd, DrawList};
+pub use encoding::Encoding;
 use fontset::Fontset;
+pub use fontset::{
+    Backend, FontChunks, FontDescription, FontHandle, FontOptions, LcdFilter, Subpixel,
+};
+pub use frame::Frame;
+use layers::Layer;
+pub use layers::LayerId;
+use layout::{runs_extent, PositionedGlyphRun};
+pub use layout::{Extent, GlyphRun};
+pub use metrics::Metrics;
+pub use shapes::Direction;
+use style::set_default_gc_state;
+pub use style::{FillStyle, Stipple};
+use visual::VisualInfo;
+use xerror::trap_errors;
+pub use xerror::XErrorInfo;
+
+// Offsets of the copies of text drawn by Draw::draw_text_outlined to form the outline
+const OUTLINE_OFFSETS: [(i32, i32); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
 
 pub(crate) const SCREEN: i32 = 0;
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
+    #[error("Character index {index} is out of bounds for text of {len} characters")]
+    CharIndexOutOfBounds { index: usize, len: usize },
+
+    #[error("Unable to flush {r:?} as it is outside of the {w}x{h} drawable")]
+    FlushOutOfBounds { r: Rect, w: u32, h: u32 },
+
+    #[error("Unable to flush to {0:#x} as it is not a valid window")]
+    FlushTargetInvalid(u32),
+
+    #[error("Font index {index} is out of bounds for {len} loaded fonts")]
+    FontIndexOutOfBounds { index: usize, len: usize },
+
+    #[error(
+        "Requested font family '{requested}' is not installed: fontconfig substituted '{got}'"
+    )]
+    FontSubstituted { requested: String, got: String },
+
+    #[error("Unable to decode image: {0}")]
+    ImageDecode(String),
+
+    #[error("'{start}'..='{end}' is not a valid character range")]
+    InvalidCharRange { start: char, end: char },
+
+    #[error("Dash patterns must be non-empty and contain no zero length segments")]
+    InvalidDashes,
+
+    #[error("'{0}' is not a valid #RRGGBB hex color")]
+    InvalidHexColor(String),
+
+    #[error("Image data was {got} bytes but {expected} were expected for its dimensions")]
+    InvalidImageData { expected: usize, got: usize },
+
+    #[error("Invalid stipple data for a {w}x{h} pattern")]
+    InvalidStipple { w: u32, h: u32 },
+
+    #[error("No 32-bit TrueColor visual is available")]
+    NoArgbVisual,
+
     #[error("Unable to find a fallback font for '{0}'")]
     NoFallbackFontForChar(char),
 
+    #[error("No TrueColor visual with a depth of {0} is available")]
+    NoVisualForDepth(i32),
+
     #[error(transparent)]
     NulError(#[from] NulError),
 
+    #[error("{r:?} is outside of the {w}x{h} drawable")]
+    OutOfBounds { r: Rect, w: u32, h: u32 },
+
     #[error("Unable to allocate the requested color using Xft")]
     UnableToAllocateColor,
 
-    #[error("Unable to open '{0}' as a font using Xft")]
+    #[error("Unable to create an XImage for the provided data")]
+    UnableToCreateImage,
+
+    #[error("Unable to open a connection to the X server")]
+    UnableToOpenDisplay,
+
+    #[error("No installed font could be opened for '{0}'")]
     UnableToOpenFont(String),
 
     #[error("Unable to open font from FcPattern using Xft")]
     UnableToOpenFontPattern,
 
-    #[error("Unable to parse '{0}' as an Xft font patten")]
+    #[error("Unable to parse '{0}' as an Xft font pattern")]
     UnableToParseFontPattern(String),
 
+    #[error("Unable to read back the full {w}x{h} drawable")]
+    UnableToReadDrawable { w: u32, h: u32 },
+
+    #[error("Unable to reload the fontconfig configuration and font list")]
+    UnableToReinitializeFontconfig,
+
+    #[error("Unable to decode text as {0:?}")]
+    UndecodableText(Encoding),
+
     #[error("'{0}' is not a registered colorscheme")]
     UnknownColorscheme(String),
+
+    #[error("{0} is not a layer of this Draw")]
+    UnknownLayer(usize),
+
+    #[error("{0} bits per pixel is not a supported image format")]
+    UnsupportedPixelFormat(i32),
+
+    #[error("Visual class {0} is not supported: a TrueColor or DirectColor visual is required")]
+    UnsupportedVisual(i32),
+
+    #[error("X error {} for request {}.{}", .0.error_code, .0.request_code, .0.minor_code)]
+    X(XErrorInfo),
 }
 
 type Result<T> = std::result::Result<T, Error>;
@@ -47,62 +184,178 @@ type Result<T> = std::result::Result<T, Error>;
 #[derive(Debug)]
 struct ColorScheme {
     name: String,
-    fg: *mut XftColor,
-    bg: *mut XftColor,
+    fg: Color,
+    bg: Color,
+    alt: Option<(Color, Color)>,
+    font: Option<String>,
 }
 
 impl ColorScheme {
     // TODO: should accept impl Into<penrose::Color>
-    fn try_new(dpy: *mut Display, name: &str, fg: &str, bg: &str) -> Result<Self> {
-        let (fg, bg) = unsafe {
-            (
-                try_xftcolor_from_name(dpy, fg)?,
-                try_xftcolor_from_name(dpy, bg)?,
-            )
-        };
+    fn try_new(dpy: *mut Display, vis: VisualInfo, name: &str, fg: &str, bg: &str) -> Result<Self> {
+        Ok(ColorScheme {
+            name: name.to_string(),
+            fg: Color::from_name(dpy, vis, fg)?,
+            bg: Color::from_name(dpy, vis, bg)?,
+            alt: None,
+            font: None,
+        })
+    }
+
+    fn try_new_with_alpha(
+        dpy: *mut Display,
+        vis: VisualInfo,
+        name: &str,
+        (fg, fg_alpha): (&str, u8),
+        (bg, bg_alpha): (&str, u8),
+    ) -> Result<Self> {
+        Ok(ColorScheme {
+            name: name.to_string(),
+            fg: Color::from_name_with_alpha(dpy, vis, fg, fg_alpha)?,
+            bg: Color::from_name_with_alpha(dpy, vis, bg, bg_alpha)?,
+            alt: None,
+            font: None,
+        })
+    }
 
+    fn try_new_from_argb(
+        dpy: *mut Display,
+        vis: VisualInfo,
+        name: &str,
+        fg: u32,
+        bg: u32,
+    ) -> Result<Self> {
         Ok(ColorScheme {
             name: name.to_string(),
-            fg,
-            bg,
+            fg: Color::from_argb(dpy, vis, fg)?,
+            bg: Color::from_argb(dpy, vis, bg)?,
+            alt: None,
+            font: None,
+        })
+    }
+
+    fn realloc(&self, dpy: *mut Display, vis: VisualInfo) -> Result<Self> {
+        Ok(ColorScheme {
+            name: self.name.clone(),
+            fg: self.fg.realloc(dpy, vis)?,
+            bg: self.bg.realloc(dpy, vis)?,
+            alt: match &self.alt {
+                Some((fg, bg)) => Some((fg.realloc(dpy, vis)?, bg.realloc(dpy, vis)?)),
+                None => None,
+            },
+            font: self.font.clone(),
         })
     }
 
-    unsafe fn fg(&self) -> u64 {
-        (*self.fg).pixel
+    // Schemes without alternate colors use their normal colors for every state
+    fn colors(&self, state: SchemeState) -> (&Color, &Color) {
+        match (state, &self.alt) {
+            (SchemeState::Alt, Some((fg, bg))) => (fg, bg),
+            _ => (&self.fg, &self.bg),
+        }
+    }
+
+    fn fg(&self) -> u64 {
+        self.fg.pixel()
     }
 
-    unsafe fn bg(&self) -> u64 {
-        (*self.bg).pixel
+    fn bg(&self) -> u64 {
+        self.bg.pixel()
     }
 }
 
-unsafe fn try_xftcolor_from_name(dpy: *mut Display, color: &str) -> Result<*mut XftColor> {
-    // https://doc.rust-lang.org/std/alloc/trait.GlobalAlloc.html#tymethod.alloc
-    let layout = Layout::new::<XftColor>();
-    let ptr = alloc(layout);
-    if ptr.is_null() {
-        handle_alloc_error(layout);
+/// Which pair of colors to use from a colorscheme added with
+/// [Draw::add_stateful_colorscheme].
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum SchemeState {
+    /// The normal fg and bg colors
+    #[default]
+    Normal,
+    /// The alternate fg and bg colors, e.g. for a hovered or pressed button
+    Alt,
+}
+
+/// How draw calls that extend beyond the current size of the drawable are handled.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum ResizeMode {
+    /// Leave the drawable as it is: anything outside of it is silently clipped
+    #[default]
+    Clip,
+    /// Grow the drawable so that it is large enough to contain the draw
+    Auto,
+    /// Return [Error::OutOfBounds] without drawing anything
+    Strict,
+}
+
+/// Horizontal placement of text within the rect it is drawn in.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+impl Align {
+    // The offset from the left edge of `available` at which content of width `w` starts.
+    // Content that doesn't fit is left aligned.
+    pub(crate) fn offset(&self, w: i32, available: u32) -> i32 {
+        let spare = (available as i32).saturating_sub(w).max(0);
+
+        match self {
+            Self::Left => 0,
+            Self::Center => spare / 2,
+            Self::Right => spare,
+        }
     }
+}
+
+/// A clockwise rotation applied to text drawn with [Draw::draw_text_rotated].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Rotation {
+    /// Reading from top to bottom
+    R90,
+    /// Upside down
+    R180,
+    /// Reading from bottom to top
+    R270,
+}
 
-    let c_name = CString::new(color)?;
-    let res = XftColorAllocName(
-        dpy,
-        XDefaultVisual(dpy, SCREEN),
-        XDefaultColormap(dpy, SCREEN),
-        c_name.as_ptr(),
-        ptr as *mut XftColor,
-    );
+/// The direction in which a widget such as a progress bar is laid out.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum Orientation {
+    #[default]
+    Horizontal,
+    Vertical,
+}
+
+/// Independent insets for each side of a [Rect], as used by [Draw::draw_text_padded].
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Padding {
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+    pub left: u32,
+}
 
-    if res == 0 {
-        Err(Error::UnableToAllocateColor)
-    } else {
-        Ok(ptr as *mut XftColor)
+impl Padding {
+    /// The same padding on every side.
+    pub fn uniform(px: u32) -> Self {
+        Self {
+            top: px,
+            right: px,
+            bottom: px,
+            left: px,
+        }
     }
 }
 
 // TODO: just use the penrose Rect struct once this is moved over
-#[derive(Debug, Copy, Clone)]
+/// A region of the drawable with its top left corner at (x, y).
+///
+/// Geometry helpers saturate rather than overflowing: a rect can shrink to zero size but
+/// never wraps around to a huge one.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
 pub struct Rect {
     pub x: i32,
     pub y: i32,
@@ -110,189 +363,1840 @@ pub struct Rect {
     pub h: u32,
 }
 
+impl Rect {
+    /// A rect of the given size positioned at the origin.
+    pub fn from_size(w: u32, h: u32) -> Self {
+        Self { x: 0, y: 0, w, h }
+    }
+
+    /// This rect inset by `px` pixels on every side. Shrinking by more than half of a
+    /// dimension leaves it zero sized (centered on the original rect) rather than wrapping.
+    pub fn shrink(&self, px: u32) -> Self {
+        let dx = px.min(self.w / 2);
+        let dy = px.min(self.h / 2);
+
+        Self {
+            x: self.x.saturating_add_unsigned(dx),
+            y: self.y.saturating_add_unsigned(dy),
+            w: self.w.saturating_sub(px.saturating_mul(2)),
+            h: self.h.saturating_sub(px.saturating_mul(2)),
+        }
+    }
+
+    /// This rect with each side inset by the given [Padding]. Padding larger than a dimension
+    /// leaves it zero sized rather than wrapping.
+    pub fn padded(&self, p: Padding) -> Self {
+        Self {
+            x: self.x.saturating_add_unsigned(p.left.min(self.w)),
+            y: self.y.saturating_add_unsigned(p.top.min(self.h)),
+            w: self.w.saturating_sub(p.left).saturating_sub(p.right),
+            h: self.h.saturating_sub(p.top).saturating_sub(p.bottom),
+        }
+    }
+
+    /// Split this rect into the `dx` pixels wide section on the left and whatever remains on
+    /// the right. `dx` is clamped to the width of the rect so the right hand side may be
+    /// zero width.
+    pub fn split_at_x(&self, dx: u32) -> (Self, Self) {
+        let dx = dx.min(self.w);
+        let left = Self { w: dx, ..*self };
+        let right = Self {
+            x: self.x.saturating_add_unsigned(dx),
+            w: self.w - dx,
+            ..*self
+        };
+
+        (left, right)
+    }
+
+    /// Split this rect into the `dy` pixels tall section at the top and whatever remains
+    /// below it. `dy` is clamped to the height of the rect so the bottom may be zero height.
+    pub fn split_at_y(&self, dy: u32) -> (Self, Self) {
+        let dy = dy.min(self.h);
+        let top = Self { h: dy, ..*self };
+        let bottom = Self {
+            y: self.y.saturating_add_unsigned(dy),
+            h: self.h - dy,
+            ..*self
+        };
+
+        (top, bottom)
+    }
+
+    /// Whether the point (x, y) lies within this rect. The right and bottom edges are
+    /// exclusive.
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        let (x, y) = (x as i64, y as i64);
+        let (left, top) = (self.x as i64, self.y as i64);
+
+        x >= left && y >= top && x < left + self.w as i64 && y < top + self.h as i64
+    }
+
+    /// The smallest rect containing both this rect and `other`. Rects with a width or
+    /// height of zero cover no area so they are ignored unless both are empty.
+    pub fn union(&self, other: Rect) -> Self {
+        if other.w == 0 || other.h == 0 {
+            return *self;
+        } else if self.w == 0 || self.h == 0 {
+            return other;
+        }
+
+        let x0 = self.x.min(other.x);
+        let y0 = self.y.min(other.y);
+        let x1 = (self.x as i64 + self.w as i64).max(other.x as i64 + other.w as i64);
+        let y1 = (self.y as i64 + self.h as i64).max(other.y as i64 + other.h as i64);
+
+        Self {
+            x: x0,
+            y: y0,
+            w: (x1 - x0 as i64).min(u32::MAX as i64) as u32,
+            h: (y1 - y0 as i64).min(u32::MAX as i64) as u32,
+        }
+    }
+
+    /// The smallest rect containing all of the given rects (see [Rect::union]), or `None` if
+    /// there aren't any.
+    pub fn bounding(rects: &[Rect]) -> Option<Self> {
+        let (first, rest) = rects.split_first()?;
+
+        Some(rest.iter().fold(*first, |acc, &r| acc.union(r)))
+    }
+
+    // The part of this rect that lies within a w x h area at the origin, if any. Xlib
+    // truncates coordinates to 16 bits so anything far enough outside of the drawable would
+    // otherwise wrap around into it.
+    pub(crate) fn clamped_to(&self, w: u32, h: u32) -> Option<Rect> {
+        let x0 = (self.x as i64).max(0);
+        let y0 = (self.y as i64).max(0);
+        let x1 = (self.x as i64 + self.w as i64).min(w as i64);
+        let y1 = (self.y as i64 + self.h as i64).min(h as i64);
+        if x1 <= x0 || y1 <= y0 {
+            return None;
+        }
+
+        Some(Rect {
+            x: x0 as i32,
+            y: y0 as i32,
+            w: (x1 - x0) as u32,
+            h: (y1 - y0) as u32,
+        })
+    }
+
+    pub(crate) fn as_xrectangle(&self) -> XRectangle {
+        XRectangle {
+            x: saturate_i16(self.x),
+            y: saturate_i16(self.y),
+            width: self.w.min(u16::MAX as u32) as u16,
+            height: self.h.min(u16::MAX as u32) as u16,
+        }
+    }
+}
+
+// Xlib coordinates are 16 bit: saturate rather than truncate so that out of range values
+// stay out of range rather than wrapping back into the drawable
+pub(crate) fn saturate_i16(v: i32) -> i16 {
+    v.clamp(i16::MIN as i32, i16::MAX as i32) as i16
+}
+
 #[derive(Debug)]
 pub struct Draw {
     dpy: *mut Display,
     root: Window,
+    vis: VisualInfo,
     drawable: Drawable,
+    owns_drawable: bool,
+    w: u32,
+    h: u32,
+    resize_mode: ResizeMode,
     gc: GC,
     fs: Fontset,
+    default_font: String,
     schemes: Vec<ColorScheme>,
+    colors: HashMap<String, Color>,
+    default_scheme: usize,
+    active_scheme: Option<usize>,
+    translations: Vec<(i32, i32)>,
+    line_spacing: f64,
+    letter_spacing: i32,
+    tab_width: u32,
+    stipple: Pixmap,
+    bg_image: Pixmap,
+    transparent_text_bg: bool,
+    layers: Vec<Layer>,
+    active_layer: Option<usize>,
+    regions: Vec<(String, Rect)>,
+    clips: Vec<Region>,
+    closed: bool,
+    #[cfg(debug_assertions)]
+    thread: std::thread::ThreadId,
 }
 
 impl Draw {
     pub fn new(root: u32, w: u32, h: u32, fnt: &str) -> Result<Self> {
+        let dpy = open_display()?;
+        let vis = unsafe { VisualInfo::default_for(dpy) };
+
+        Self::new_with_visual(dpy, root as Window, vis, None, w, h, fnt, Backend::Xft)
+    }
+
+    /// Create a new [Draw] that renders text using the core X11 font `fnt` rather than Xft.
+    /// See [Backend] for the tradeoffs involved.
+    pub fn new_core_font(root: u32, w: u32, h: u32, fnt: &str) -> Result<Self> {
+        let dpy = open_display()?;
+        let vis = unsafe { VisualInfo::default_for(dpy) };
+
+        Self::new_with_visual(dpy, root as Window, vis, None, w, h, fnt, Backend::Core)
+    }
+
+    /// Create a new [Draw] that renders using a 32-bit ARGB visual rather than the default
+    /// visual of the screen, allowing for per-pixel transparency when a compositor is running.
+    ///
+    /// Windows that are flushed to must be created with the same visual and depth (see
+    /// [Draw::raw_visual]).
+    pub fn new_argb(root: u32, w: u32, h: u32, fnt: &str) -> Result<Self> {
+        let root = root as Window;
+        let dpy = open_display()?;
+        let vis = match unsafe { VisualInfo::argb(dpy, root) } {
+            Some(vis) => vis,
+            None => {
+                unsafe { XCloseDisplay(dpy) };
+                return Err(Error::NoArgbVisual);
+            }
+        };
+
+        Self::new_with_visual(dpy, root, vis, None, w, h, fnt, Backend::Xft)
+    }
+
+    /// Create a new [Draw] whose pixmap has the given depth rather than the default depth of
+    /// the screen, using a TrueColor visual of that depth.
+    ///
+    /// [Draw::flush_to] copies the pixmap directly so windows that are flushed to must have
+    /// the same depth (and visual, see [Draw::raw_visual]) or the copy fails with BadMatch.
+    pub fn new_with_depth(root: u32, w: u32, h: u32, fnt: &str, depth: i32) -> Result<Self> {
         let root = root as Window;
-        let (dpy, drawable, gc) = unsafe {
-            let dpy = XOpenDisplay(std::ptr::null());
-            let depth = XDefaultDepth(dpy, SCREEN) as u32;
-            let drawable = XCreatePixmap(dpy, root, w, h, depth);
-            let gc = XCreateGC(dpy, root, 0, std::ptr::null_mut());
-            XSetLineAttributes(dpy, gc, 1, LineSolid, CapButt, JoinMiter);
-
-            (dpy, drawable, gc)
+        let dpy = open_display()?;
+        let vis = match unsafe { VisualInfo::for_depth(dpy, root, depth) } {
+            Some(vis) => vis,
+            None => {
+                unsafe { XCloseDisplay(dpy) };
+                return Err(Error::NoVisualForDepth(depth));
+            }
+        };
+
+        Self::new_with_visual(dpy, root, vis, None, w, h, fnt, Backend::Xft)
+    }
+
+    /// Create a new [Draw] that renders into an existing `w` x `h` pixmap owned by someone
+    /// else (such as another [Draw]) rather than allocating its own.
+    ///
+    /// The pixmap must have the depth of the default visual and is not freed when this [Draw]
+    /// is dropped. Calling [Draw::resize] (including via [ResizeMode::Auto]) replaces it with
+    /// a new pixmap that is owned by this [Draw].
+    pub fn new_with_drawable(root: u32, drawable: u32, w: u32, h: u32, fnt: &str) -> Result<Self> {
+        let dpy = open_display()?;
+        let vis = unsafe { VisualInfo::default_for(dpy) };
+        let drawable = Some(drawable as Drawable);
+
+        Self::new_with_visual(dpy, root as Window, vis, drawable, w, h, fnt, Backend::Xft)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn new_with_visual(
+        dpy: *mut Display,
+        root: Window,
+        vis: VisualInfo,
+        drawable: Option<Drawable>,
+        w: u32,
+        h: u32,
+        fnt: &str,
+        backend: Backend,
+    ) -> Result<Self> {
+        let fs = match Fontset::try_new(dpy, fnt, FontOptions::default(), backend) {
+            Ok(fs) => fs,
+            Err(e) => {
+                unsafe {
+                    vis.free(dpy);
+                    XCloseDisplay(dpy);
+                }
+                return Err(e);
+            }
+        };
+
+        let owns_drawable = drawable.is_none();
+        let (drawable, gc) = unsafe {
+            match drawable {
+                Some(d) => (d, create_gc(dpy, d)),
+                None => create_drawable(dpy, root, vis, w, h),
+            }
         };
 
         Ok(Self {
             dpy,
             root,
+            vis,
             drawable,
+            owns_drawable,
+            w,
+            h,
+            resize_mode: ResizeMode::default(),
             gc,
-            fs: Fontset::try_new(dpy, fnt)?,
+            default_font: fs.name().to_string(),
+            fs,
             schemes: Vec::new(),
+            colors: HashMap::new(),
+            default_scheme: 0,
+            active_scheme: None,
+            translations: Vec::new(),
+            line_spacing: 1.0,
+            letter_spacing: 0,
+            tab_width: 8,
+            stipple: 0,
+            bg_image: 0,
+            transparent_text_bg: false,
+            layers: Vec::new(),
+            active_layer: None,
+            regions: Vec::new(),
+            clips: Vec::new(),
+            closed: false,
+            #[cfg(debug_assertions)]
+            thread: std::thread::current().id(),
         })
     }
 
+    /// Resize the drawable, keeping whatever content still fits. Newly exposed areas are left
+    /// undefined until they are drawn to.
     pub fn resize(&mut self, w: u32, h: u32) {
+        self.assert_thread();
         unsafe {
+            let drawable = XCreatePixmap(self.dpy, self.root, w, h, self.vis.depth as u32);
+            log_debug!(
+                "resizing pixmap {} from {}x{} to {w}x{h} (new pixmap {drawable})",
+                self.drawable,
+                self.w,
+                self.h
+            );
+
             if self.drawable != 0 {
-                XFreePixmap(self.dpy, self.drawable);
+                let (cw, ch) = (w.min(self.w), h.min(self.h));
+                self.suspend_clip();
+                XCopyArea(
+                    self.dpy,
+                    self.drawable,
+                    drawable,
+                    self.gc,
+                    0,
+                    0,
+                    cw,
+                    ch,
+                    0,
+                    0,
+                );
+                self.resume_clip();
+                if self.owns_drawable {
+                    XFreePixmap(self.dpy, self.drawable);
+                }
             }
 
-            let depth = XDefaultDepth(self.dpy, SCREEN) as u32;
-            self.drawable = XCreatePixmap(self.dpy, self.root, w, h, depth);
+            self.drawable = drawable;
+            self.owns_drawable = true;
+        }
+
+        self.w = w;
+        self.h = h;
+    }
+
+    /// Set how draw calls that extend beyond the current size of the drawable are handled.
+    pub fn set_resize_mode(&mut self, mode: ResizeMode) {
+        self.resize_mode = mode;
+    }
+
+    /// Toggle between [ResizeMode::Auto] and the default [ResizeMode::Clip].
+    pub fn set_auto_resize(&mut self, auto_resize: bool) {
+        self.resize_mode = if auto_resize {
+            ResizeMode::Auto
+        } else {
+            ResizeMode::Clip
+        };
+    }
+
+    // Check a region that has already had the current translation applied against the size
+    // of the drawable. Only the right and bottom edges are considered as regions with a
+    // negative origin can never be made to fit by growing the drawable.
+    fn ensure_fits(&mut self, r: Rect) -> Result<()> {
+        let right = (r.x as i64 + r.w as i64).max(0) as u32;
+        let bottom = (r.y as i64 + r.h as i64).max(0) as u32;
+        if right <= self.w && bottom <= self.h {
+            return Ok(());
+        }
+
+        match self.resize_mode {
+            ResizeMode::Clip => Ok(()),
+            ResizeMode::Auto => {
+                self.resize(self.w.max(right), self.h.max(bottom));
+                Ok(())
+            }
+            ResizeMode::Strict => Err(Error::OutOfBounds {
+                r,
+                w: self.w,
+                h: self.h,
+            }),
         }
     }
 
+    /// The [Backend] used for rendering text.
+    pub fn backend(&self) -> Backend {
+        self.fs.backend()
+    }
+
+    /// Check whether the given font name resolves to an installed font of the requested family
+    /// rather than a substitute chosen by fontconfig.
+    pub fn font_available(&self, font_name: &str) -> bool {
+        fontset::font_available(self.dpy, font_name)
+    }
+
+    /// Load the given font and use it as the default font for colorschemes that don't have
+    /// one of their own (see [Draw::add_colorscheme_with_font]).
     pub fn set_font(&mut self, font_name: &str) -> Result<()> {
-        self.fs = Fontset::try_new(self.dpy, font_name)?;
+        self.assert_thread();
+        self.fs = self.fs.reload(font_name, self.fs.opts())?;
+        self.default_font = font_name.to_string();
 
         Ok(())
     }
 
-    pub fn set_colorscheme(&mut self, scheme: &str) -> Result<()> {
-        let ix = self
-            .schemes
+    /// Reload the fontconfig configuration and rescan the installed fonts before loading the
+    /// current font again, so that fonts installed since this Draw was created are picked up
+    /// by font matching and fallback resolution.
+    pub fn reload_fonts(&mut self) -> Result<()> {
+        self.assert_thread();
+        fontset::reinitialize_fontconfig()?;
+        self.fs = self.fs.reload(self.fs.name(), self.fs.opts())?;
+
+        Ok(())
+    }
+
+    /// Render all characters from `start` to `end` (inclusive) that are supported by the
+    /// given font using it in preference to any other font, including the primary font.
+    /// Characters it doesn't cover fall back to the usual font matching.
+    pub fn add_range_font(&mut self, start: char, end: char, pattern: &str) -> Result<()> {
+        self.assert_thread();
+        self.fs.add_range_font(start, end, pattern)
+    }
+
+    /// Prefer fallback fonts that support the given language (an RFC 3066 tag such as "ja" or
+    /// "zh-cn") when finding a font for characters the primary font can't render, so that
+    /// characters shared between languages use the intended regional forms.
+    ///
+    /// Passing an empty string removes the preference. Fallback fonts already picked by
+    /// automatic matching are dropped and chosen again the next time they are needed.
+    pub fn set_language(&mut self, lang: &str) -> Result<()> {
+        self.assert_thread();
+        let lang = if lang.is_empty() { None } else { Some(lang) };
+        self.fs = self.fs.with_language(lang)?;
+
+        Ok(())
+    }
+
+    /// Reload the current font with the given [FontOptions] applied to its pattern.
+    pub fn set_font_options(&mut self, opts: FontOptions) -> Result<()> {
+        self.assert_thread();
+        self.fs = self.fs.reload(self.fs.name(), opts)?;
+
+        Ok(())
+    }
+
+    fn scheme_index(&self, scheme: &str) -> Result<usize> {
+        self.schemes
             .iter()
-            .enumerate()
-            .find(|(_, s)| s.name == scheme)
-            .map(|(i, _)| i)
-            .ok_or_else(|| Error::UnknownColorscheme(scheme.to_string()))?;
+            .position(|s| s.name == scheme)
+            .ok_or_else(|| Error::UnknownColorscheme(scheme.to_string()))
+    }
+
+    /// Make the named colorscheme active for all subsequent draw calls, overriding the
+    /// default scheme until [Draw::reset_colorscheme] is called.
+    pub fn set_colorscheme(&mut self, scheme: &str) -> Result<()> {
+        let ix = self.scheme_index(scheme)?;
+        self.use_scheme_font(ix)?;
+        self.active_scheme = Some(ix);
+
+        Ok(())
+    }
+
+    /// Set the colorscheme used by draw calls when no other scheme has been made active
+    /// using [Draw::set_colorscheme]. Until this is called the first scheme to be added is
+    /// used as the default.
+    pub fn set_default_scheme(&mut self, name: &str) -> Result<()> {
+        let ix = self.scheme_index(name)?;
+        if self.active_scheme.is_none() {
+            self.use_scheme_font(ix)?;
+        }
+        self.default_scheme = ix;
+
+        Ok(())
+    }
+
+    /// Go back to drawing with the default colorscheme.
+    ///
+    /// If this needs to switch fonts and the font fails to load then the current scheme and
+    /// font are both kept and the error is logged.
+    pub fn reset_colorscheme(&mut self) {
+        if self.schemes.is_empty() {
+            self.active_scheme = None;
+            return;
+        }
+
+        match self.use_scheme_font(self.default_scheme) {
+            Ok(()) => self.active_scheme = None,
+            Err(e) => {
+                log_error!("unable to load the font for the default scheme: {e}");
+            }
+        }
+    }
 
-        if ix != 0 {
-            self.schemes.swap(0, ix);
+    // Switch to the font of the scheme with the given index, or the default font if it
+    // doesn't have one of its own
+    fn use_scheme_font(&mut self, ix: usize) -> Result<()> {
+        let font = match &self.schemes[ix].font {
+            Some(font) => font,
+            None => &self.default_font,
+        };
+        if font != self.fs.name() {
+            self.fs = self.fs.reload(font, self.fs.opts())?;
         }
 
         Ok(())
     }
 
+    // The scheme that draw calls should use
+    fn scheme(&self) -> &ColorScheme {
+        &self.schemes[self.active_scheme.unwrap_or(self.default_scheme)]
+    }
+
     pub fn add_colorscheme(&mut self, name: &str, fg: &str, bg: &str) -> Result<()> {
-        let cs = ColorScheme::try_new(self.dpy, name, fg, bg)?;
+        self.assert_thread();
+        let cs = ColorScheme::try_new(self.dpy, self.vis, name, fg, bg)?;
         self.schemes.push(cs);
 
         Ok(())
     }
 
-    pub fn draw_rect(&mut self, Rect { x, y, w, h }: Rect, inverted: bool) -> Result<()> {
-        let scheme = &self.schemes[0];
+    /// Add a colorscheme that also switches to the given font whenever it is made active,
+    /// going back to the default font when a scheme without a font of its own is used.
+    ///
+    /// The font is loaded again each time it is switched to so it is best to group draw
+    /// calls by scheme.
+    pub fn add_colorscheme_with_font(
+        &mut self,
+        name: &str,
+        fg: &str,
+        bg: &str,
+        font: &str,
+    ) -> Result<()> {
+        self.assert_thread();
+        let mut cs = ColorScheme::try_new(self.dpy, self.vis, name, fg, bg)?;
+        cs.font = Some(font.to_string());
+        self.schemes.push(cs);
 
-        unsafe {
-            let pixel = if inverted { scheme.bg() } else { scheme.fg() };
-            XSetForeground(self.dpy, self.gc, pixel);
-            XDrawRectangle(self.dpy, self.drawable, self.gc, x, y, w, h);
+        Ok(())
+    }
+
+    /// Add multiple colorschemes at once from (name, fg, bg) triples, stopping at the first
+    /// that fails to allocate.
+    ///
+    /// This accepts owned or borrowed strings so palettes can be generated programmatically.
+    pub fn add_colorschemes<I, S>(&mut self, schemes: I) -> Result<()>
+    where
+        I: IntoIterator<Item = (S, S, S)>,
+        S: AsRef<str>,
+    {
+        for (name, fg, bg) in schemes {
+            self.add_colorscheme(name.as_ref(), fg.as_ref(), bg.as_ref())?;
         }
 
         Ok(())
     }
 
-    pub fn fill_rect(&mut self, Rect { x, y, w, h }: Rect, invert: bool) -> Result<()> {
-        let scheme = &self.schemes[0];
+    /// Add a colorscheme from named colors with an alpha value for each, where 0 is fully
+    /// transparent and 255 is fully opaque.
+    ///
+    /// Transparency is only visible in the final window when using [Draw::new_argb] with a
+    /// compositor running, but the alpha of the foreground is always used when blending
+    /// antialiased text.
+    pub fn add_colorscheme_with_alpha(
+        &mut self,
+        name: &str,
+        fg: &str,
+        fg_alpha: u8,
+        bg: &str,
+        bg_alpha: u8,
+    ) -> Result<()> {
+        self.assert_thread();
+        let (fg, bg) = ((fg, fg_alpha), (bg, bg_alpha));
+        let cs = ColorScheme::try_new_with_alpha(self.dpy, self.vis, name, fg, bg)?;
+        self.schemes.push(cs);
 
-        unsafe {
-            let pixel = if invert { scheme.bg() } else { scheme.fg() };
-            XSetForeground(self.dpy, self.gc, pixel);
-            XFillRectangle(self.dpy, self.drawable, self.gc, x, y, w, h);
-        }
+        Ok(())
+    }
+
+    /// Add a colorscheme using `0xRRGGBB` color values.
+    pub fn add_colorscheme_u32(&mut self, name: &str, fg: u32, bg: u32) -> Result<()> {
+        self.add_colorscheme_argb(name, 0xff000000 | fg, 0xff000000 | bg)
+    }
+
+    /// Add a colorscheme using `0xAARRGGBB` color values.
+    pub fn add_colorscheme_argb(&mut self, name: &str, fg: u32, bg: u32) -> Result<()> {
+        self.assert_thread();
+        let cs = ColorScheme::try_new_from_argb(self.dpy, self.vis, name, fg, bg)?;
+        self.schemes.push(cs);
 
         Ok(())
     }
 
-    pub fn show_font_match_for_chars(&mut self, txt: &str) {
-        for (chunk, fm) in self.fs.per_font_chunks(txt) {
-            let ext = self.fs.fnt(fm).get_exts(self.dpy, chunk);
-            println!("{fm:?} [extent: {ext:?}] -> '{chunk}'");
-        }
+    /// Add a colorscheme carrying an alternate pair of colors alongside its normal ones so
+    /// that related looks (such as the normal and hovered states of a button) can share a
+    /// single name. Draw calls using the scheme via [Draw::set_colorscheme] use the normal
+    /// colors: use [Draw::fill_rect_state] to select between the two.
+    pub fn add_stateful_colorscheme(
+        &mut self,
+        name: &str,
+        normal_fg: &str,
+        normal_bg: &str,
+        alt_fg: &str,
+        alt_bg: &str,
+    ) -> Result<()> {
+        self.assert_thread();
+        let mut cs = ColorScheme::try_new(self.dpy, self.vis, name, normal_fg, normal_bg)?;
+        cs.alt = Some((
+            Color::from_name(self.dpy, self.vis, alt_fg)?,
+            Color::from_name(self.dpy, self.vis, alt_bg)?,
+        ));
+        self.schemes.push(cs);
+
+        Ok(())
     }
 
-    // TODO: Need to bounds checks
-    // https://keithp.com/~keithp/talks/xtc2001/xft.pdf
-    // https://keithp.com/~keithp/render/Xft.tutorial
-    pub fn draw_text(&mut self, txt: &str, lpad: u32, r: Rect, invert: bool) -> Result<()> {
-        self.fill_rect(r, !invert)?; // !invert so we get the other color
+    /// Fill `r` with the background color of the given state of the named colorscheme.
+    ///
+    /// Schemes without alternate colors use their normal background for both states.
+    pub fn fill_rect_state(&mut self, scheme: &str, state: SchemeState, r: Rect) -> Result<()> {
+        let (_, bg) = self.schemes[self.scheme_index(scheme)?].colors(state);
+        let pixel = bg.pixel();
+        let r = self.translate_rect(r);
+        self.ensure_fits(r)?;
+        self.fill_pixel(r, pixel);
 
-        unsafe {
-            let d = XftDrawCreate(
-                self.dpy,
-                self.drawable,
-                XDefaultVisual(self.dpy, SCREEN),
-                XDefaultColormap(self.dpy, SCREEN),
-            );
+        Ok(())
+    }
 
-            let scheme = &self.schemes[0];
-            let color = if invert { scheme.bg } else { scheme.fg };
-            let Rect { mut x, y, h, .. } = r;
-            x += lpad as i32;
-
-            for (chunk, fm) in self.fs.per_font_chunks(txt).into_iter() {
-                let fnt = self.fs.fnt(fm);
-                let (chunk_w, chunk_h) = fnt.get_exts(self.dpy, chunk)?;
-                let chunk_y = y + (h as i32 - chunk_h) / 2 + (*fnt.xfont).ascent;
-
-                let c_str = CString::new(chunk).unwrap();
-                XftDrawStringUtf8(
-                    d,
-                    color,
-                    self.fs.fnt(fm).xfont,
-                    x,
-                    chunk_y,
-                    c_str.as_ptr() as *mut _,
-                    c_str.as_bytes().len() as i32,
-                );
+    /// Offset all subsequent draw calls by (dx, dy) on top of any existing translation until
+    /// a matching call to [Draw::pop_translate].
+    pub fn push_translate(&mut self, dx: i32, dy: i32) {
+        let (x, y) = self.translation();
+        self.translations
+            .push((x.saturating_add(dx), y.saturating_add(dy)));
+    }
 
-                x += chunk_w;
-            }
-        }
+    /// Remove the most recently pushed translation (if there is one).
+    pub fn pop_translate(&mut self) {
+        self.translations.pop();
+    }
+
+    fn translation(&self) -> (i32, i32) {
+        self.translations.last().copied().unwrap_or((0, 0))
+    }
+
+    fn translate_point(&self, (x, y): (i32, i32)) -> (i32, i32) {
+        let (dx, dy) = self.translation();
+
+        (x.saturating_add(dx), y.saturating_add(dy))
+    }
+
+    fn translate_rect(&self, r: Rect) -> Rect {
+        let (x, y) = self.translate_point((r.x, r.y));
+
+        Rect { x, y, ..r }
+    }
+
+    /// Fill the entire drawable with the background image (if one is set) or the background
+    /// color of the active colorscheme.
+    pub fn clear(&mut self) -> Result<()> {
+        self.assert_thread();
+        self.fill_background(Rect {
+            x: 0,
+            y: 0,
+            w: self.w,
+            h: self.h,
+        });
 
         Ok(())
     }
 
-    pub fn text_extent(&mut self, txt: &str) -> Result<(i32, i32)> {
-        let (mut w, mut h) = (0, 0);
-        for (chunk, fm) in self.fs.per_font_chunks(txt) {
-            let (cw, ch) = self.fs.fnt(fm).get_exts(self.dpy, chunk)?;
-            w += cw;
-            h += ch;
+    pub fn draw_rect(&mut self, r: Rect, inverted: bool) -> Result<()> {
+        self.assert_thread();
+        let r = self.translate_rect(r);
+        self.ensure_fits(r)?;
+        let Rect { x, y, w, h } = r;
+        let scheme = self.scheme();
+        let pixel = if inverted { scheme.bg() } else { scheme.fg() };
+
+        unsafe {
+            XSetForeground(self.dpy, self.gc, pixel);
+            XDrawRectangle(self.dpy, self.drawable, self.gc, x, y, w, h);
         }
 
-        Ok((w, h))
+        Ok(())
     }
 
-    pub fn flush_to(&mut self, win: u32, Rect { x, y, w, h }: Rect) {
-        let win = win as Window;
+    pub fn fill_rect(&mut self, r: Rect, invert: bool) -> Result<()> {
+        let r = self.translate_rect(r);
+        self.ensure_fits(r)?;
+        self.fill(r, invert);
 
-        unsafe {
-            XCopyArea(self.dpy, self.drawable, win, self.gc, x, y, w, h, x, y);
-            XSync(self.dpy, False);
+        Ok(())
+    }
+
+    /// Fill each of the given rects in a single request.
+    pub fn fill_rects(&mut self, rects: &[Rect], invert: bool) -> Result<()> {
+        let mut translated = Vec::with_capacity(rects.len());
+        for &r in rects {
+            let r = self.translate_rect(r);
+            self.ensure_fits(r)?;
+            translated.push(r);
         }
+
+        let scheme = self.scheme();
+        let pixel = if invert { scheme.bg() } else { scheme.fg() };
+        self.fill_rects_pixel(&translated, pixel);
+
+        Ok(())
     }
 
-    unsafe fn free_colors(&mut self) {
-        let layout = Layout::new::<XftColor>();
+    /// Fill `r` with a checkerboard of `cell` sized squares alternating between the `light`
+    /// and `dark` colors, starting with `light` in the top left corner.
+    pub fn fill_checkerboard(&mut self, r: Rect, cell: u32, light: &str, dark: &str) -> Result<()> {
+        let r = self.translate_rect(r);
+        self.ensure_fits(r)?;
+        let light = self.color_pixel(light)?;
+        let dark = self.color_pixel(dark)?;
+        let cell = cell.max(1);
+        let Some(visible) = r.clamped_to(self.w, self.h) else {
+            return Ok(());
+        };
+
+        // Only the cells overlapping the drawable are generated but they are positioned
+        // relative to the origin of r so that the pattern doesn't shift when r is clipped
+        let (off_x, off_y) = (visible.x.abs_diff(r.x), visible.y.abs_diff(r.y));
+        let (mut light_cells, mut dark_cells) = (Vec::new(), Vec::new());
+        for dy in (off_y / cell * cell..off_y + visible.h).step_by(cell as usize) {
+            for dx in (off_x / cell * cell..off_x + visible.w).step_by(cell as usize) {
+                let tile = Rect {
+                    x: r.x.saturating_add_unsigned(dx),
+                    y: r.y.saturating_add_unsigned(dy),
+                    w: cell.min(r.w - dx),
+                    h: cell.min(r.h - dy),
+                };
 
-        for ColorScheme { fg, bg, .. } in self.schemes.drain(0..) {
-            for ptr in [fg, bg] {
-                dealloc(ptr as *mut u8, layout);
+                if (dx / cell) % 2 == (dy / cell) % 2 {
+                    light_cells.push(tile);
+                } else {
+                    dark_cells.push(tile);
+                }
             }
         }
+
+        self.fill_rects_pixel(&light_cells, light);
+        self.fill_rects_pixel(&dark_cells, dark);
+
+        Ok(())
     }
-}
 
-impl Drop for Draw {
-    fn drop(&mut self) {
-        unsafe {
-            XFreePixmap(self.dpy, self.drawable);
-            XFreeGC(self.dpy, self.gc);
-            self.free_colors();
-        }
+    /// Fill `r` with the named color without needing to register a colorscheme for it.
+    ///
+    /// Colors are allocated the first time they are used and then cached for later calls
+    /// until [Draw::clear_color_cache] is called.
+    pub fn fill_rect_color(&mut self, r: Rect, color: &str) -> Result<()> {
+        let pixel = self.color_pixel(color)?;
+        let r = self.translate_rect(r);
+        self.ensure_fits(r)?;
+        self.fill_pixel(r, pixel);
+
+        Ok(())
+    }
+
+    /// Free all of the cached colors allocated by methods that accept colors by value rather
+    /// than by colorscheme, such as [Draw::fill_rect_color] and [Draw::draw_bevel].
+    ///
+    /// Colors that are used again after this are reallocated on demand.
+    pub fn clear_color_cache(&mut self) {
+        self.assert_thread();
+        self.colors.clear();
+    }
+
+    // The pixel value of an ad-hoc color, allocating and caching it on first use
+    fn color_pixel(&mut self, name: &str) -> Result<u64> {
+        self.assert_thread();
+        if let Some(c) = self.colors.get(name) {
+            return Ok(c.pixel());
+        }
+
+        let c = Color::from_name(self.dpy, self.vis, name)?;
+        let pixel = c.pixel();
+        self.colors.insert(name.to_string(), c);
+
+        Ok(pixel)
+    }
+
+    // Fill rects that have already had the current translation applied
+    fn fill_rects_pixel(&mut self, rects: &[Rect], pixel: u64) {
+        self.assert_thread();
+        let mut xrects: Vec<XRectangle> = rects
+            .iter()
+            .filter_map(|r| r.clamped_to(self.w, self.h))
+            .map(|r| r.as_xrectangle())
+            .collect();
+        if xrects.is_empty() {
+            return;
+        }
+
+        unsafe {
+            XSetForeground(self.dpy, self.gc, pixel);
+            XFillRectangles(
+                self.dpy,
+                self.drawable,
+                self.gc,
+                xrects.as_mut_ptr(),
+                xrects.len() as i32,
+            );
+        }
+    }
+
+    // Fill a rect that has already had the current translation applied
+    fn fill(&mut self, r: Rect, invert: bool) {
+        let scheme = self.scheme();
+        let pixel = if invert { scheme.bg() } else { scheme.fg() };
+
+        self.fill_pixel(r, pixel);
+    }
+
+    fn fill_pixel(&mut self, r: Rect, pixel: u64) {
+        self.assert_thread();
+        let Some(Rect { x, y, w, h }) = r.clamped_to(self.w, self.h) else {
+            return;
+        };
+
+        unsafe {
+            XSetForeground(self.dpy, self.gc, pixel);
+            XFillRectangle(self.dpy, self.drawable, self.gc, x, y, w, h);
+        }
+    }
+
+    /// Draw a progress bar filling `fraction` (clamped to 0.0..=1.0) of `r` using the
+    /// foreground color of `fg_scheme` over the background color of `bg_scheme`.
+    ///
+    /// Horizontal bars fill from left to right and vertical bars from the bottom up.
+    pub fn draw_progress(
+        &mut self,
+        r: Rect,
+        fraction: f64,
+        fg_scheme: &str,
+        bg_scheme: &str,
+        orientation: Orientation,
+    ) -> Result<()> {
+        let fg = self.schemes[self.scheme_index(fg_scheme)?].fg();
+        let bg = self.schemes[self.scheme_index(bg_scheme)?].bg();
+
+        let r = self.translate_rect(r);
+        self.ensure_fits(r)?;
+        let fraction = fraction.clamp(0.0, 1.0);
+        self.fill_pixel(r, bg);
+
+        let filled = match orientation {
+            Orientation::Horizontal => Rect {
+                w: (r.w as f64 * fraction).round() as u32,
+                ..r
+            },
+            Orientation::Vertical => {
+                let h = (r.h as f64 * fraction).round() as u32;
+                Rect {
+                    y: r.y + (r.h - h) as i32,
+                    h,
+                    ..r
+                }
+            }
+        };
+
+        if filled.w > 0 && filled.h > 0 {
+            self.fill_pixel(filled, fg);
+        }
+
+        Ok(())
+    }
+
+    pub fn draw_line(&mut self, from: (i32, i32), to: (i32, i32), invert: bool) -> Result<()> {
+        let scheme = self.scheme();
+        let pixel = if invert { scheme.bg() } else { scheme.fg() };
+
+        self.draw_line_pixel(from, to, pixel)
+    }
+
+    /// Draw a vertical divider at `x` in the foreground color of `scheme`, spanning the
+    /// height of `r` less `inset` pixels at both the top and the bottom. Nothing is drawn if
+    /// the insets leave no height.
+    pub fn draw_separator(&mut self, x: i32, r: Rect, inset: u32, scheme: &str) -> Result<()> {
+        let pixel = self.schemes[self.scheme_index(scheme)?].fg();
+        let h = r.h.saturating_sub(inset.saturating_mul(2));
+        if h == 0 {
+            return Ok(());
+        }
+        let top = r.y.saturating_add_unsigned(inset);
+        let bottom = top.saturating_add_unsigned(h - 1);
+
+        self.draw_line_pixel((x, top), (x, bottom), pixel)
+    }
+
+    fn draw_line_pixel(&mut self, from: (i32, i32), to: (i32, i32), pixel: u64) -> Result<()> {
+        self.assert_thread();
+        let (x1, y1) = self.translate_point(from);
+        let (x2, y2) = self.translate_point(to);
+        self.ensure_fits(Rect {
+            x: x1.min(x2),
+            y: y1.min(y2),
+            w: x1.abs_diff(x2) + 1,
+            h: y1.abs_diff(y2) + 1,
+        })?;
+
+        unsafe {
+            XSetForeground(self.dpy, self.gc, pixel);
+            XDrawLine(self.dpy, self.drawable, self.gc, x1, y1, x2, y2);
+        }
+
+        Ok(())
+    }
+
+    /// Draw a one pixel 3D border around the inside edge of `r`: when `raised` the top and
+    /// left edges are drawn in `light` and the bottom and right edges in `dark`, otherwise
+    /// the colors are swapped to give an inset look.
+    pub fn draw_bevel(&mut self, r: Rect, light: &str, dark: &str, raised: bool) -> Result<()> {
+        if r.w == 0 || r.h == 0 {
+            return Ok(());
+        }
+
+        let light = self.color_pixel(light)?;
+        let dark = self.color_pixel(dark)?;
+        let (top_left, bottom_right) = if raised { (light, dark) } else { (dark, light) };
+
+        let Rect { x, y, w, h } = r;
+        let (right, bottom) = (x + w as i32 - 1, y + h as i32 - 1);
+        self.draw_line_pixel((x, y), (right, y), top_left)?;
+        self.draw_line_pixel((x, y), (x, bottom), top_left)?;
+        self.draw_line_pixel((x, bottom), (right, bottom), bottom_right)?;
+        self.draw_line_pixel((right, y), (right, bottom), bottom_right)?;
+
+        Ok(())
+    }
+
+    pub fn show_font_match_for_chars(&mut self, txt: &str) {
+        self.assert_thread();
+        for (chunk, fm) in self.fs.per_font_chunks(txt) {
+            let ext = self.fs.fnt(fm).get_exts(self.dpy, chunk);
+            println!("{fm:?} [extent: {ext:?}] -> '{chunk}'");
+        }
+    }
+
+    /// Describe the primary font that the font name this [Draw] was created with resolved to.
+    pub fn font_description(&self) -> FontDescription {
+        self.fs.description()
+    }
+
+    /// The horizontal advance of a single space in the primary font, for use in indentation
+    /// and alignment calculations.
+    pub fn space_width(&mut self) -> Result<i32> {
+        self.assert_thread();
+        self.fs.space_width()
+    }
+
+    /// Split the given text into contiguous slices that are rendered using the same font,
+    /// without allocating.
+    pub fn font_chunks<'a>(&mut self, txt: &'a str) -> FontChunks<'a, '_> {
+        self.assert_thread();
+        self.fs.chunks(txt)
+    }
+
+    /// Call `f` with each contiguous slice of `txt` that is rendered using the same font along
+    /// with a handle to that font, for building custom layouts on top of the font matching
+    /// used by [Draw::draw_text].
+    pub fn for_each_chunk(&mut self, txt: &str, mut f: impl FnMut(&str, FontHandle<'_>)) {
+        self.assert_thread();
+        for (chunk, fm) in self.fs.per_font_chunks(txt) {
+            f(chunk, FontHandle::new(self.dpy, self.fs.fnt(fm), fm));
+        }
+    }
+
+    // TODO: Need to bounds checks
+    // https://keithp.com/~keithp/talks/xtc2001/xft.pdf
+    // https://keithp.com/~keithp/render/Xft.tutorial
+    pub fn draw_text(&mut self, txt: &str, lpad: u32, r: Rect, invert: bool) -> Result<()> {
+        self.assert_thread();
+        if txt.is_empty() {
+            return Ok(());
+        }
+
+        let r = self.translate_rect(r);
+        self.ensure_fits(r)?;
+        self.fill_text_background(r, invert);
+        let runs = self.layout_run(txt)?;
+
+        self.draw_positioned_runs(&runs, lpad, r, invert)
+    }
+
+    /// Draw text in the same way as [Draw::draw_text] but laid out within `r` inset by
+    /// `padding`, with the text clipped to that inset region. The background still fills
+    /// all of `r`.
+    ///
+    /// Calling [Draw::draw_text] with an `lpad` is the same as using a padding that only
+    /// has `left` set.
+    pub fn draw_text_padded(
+        &mut self,
+        txt: &str,
+        padding: Padding,
+        r: Rect,
+        invert: bool,
+    ) -> Result<()> {
+        self.assert_thread();
+        if txt.is_empty() {
+            return Ok(());
+        }
+
+        let r = self.translate_rect(r);
+        self.ensure_fits(r)?;
+        self.fill_text_background(r, invert);
+        let inner = r.padded(padding);
+        if inner.w == 0 || inner.h == 0 {
+            return Ok(());
+        }
+        let runs = self.layout_run(txt)?;
+
+        unsafe { self.push_clip_region(self.clip_region(inner)) };
+        let res = self.draw_positioned_runs(&runs, 0, inner, invert);
+        self.pop_clip();
+
+        res
+    }
+
+    /// Draw text in the same way as [Draw::draw_text] with a one pixel outline in the named
+    /// `outline` color around each glyph, so that it stays readable over any background.
+    ///
+    /// The text is rendered nine times so this is noticeably more expensive than
+    /// [Draw::draw_text].
+    pub fn draw_text_outlined(
+        &mut self,
+        txt: &str,
+        lpad: u32,
+        r: Rect,
+        outline: &str,
+        invert: bool,
+    ) -> Result<()> {
+        self.assert_thread();
+        if txt.is_empty() {
+            return Ok(());
+        }
+
+        self.color_pixel(outline)?;
+        let r = self.translate_rect(r);
+        self.ensure_fits(r)?;
+        self.fill_text_background(r, invert);
+        let runs = self.layout_run(txt)?;
+
+        unsafe {
+            let d = XftDrawCreate(self.dpy, self.drawable, self.vis.visual, self.vis.colormap);
+            self.clip_xft_draw(d);
+
+            let scheme = self.scheme();
+            let fill = if invert { &scheme.bg } else { &scheme.fg };
+            let outline = self.colors[outline].as_xft_color();
+            let (x, y) = (r.x + lpad as i32, r.y);
+
+            let res = OUTLINE_OFFSETS
+                .iter()
+                .try_for_each(|&(dx, dy)| self.draw_runs(d, outline, &runs, (x + dx, y + dy), r.h))
+                .and_then(|_| self.draw_runs(d, fill.as_xft_color(), &runs, (x, y), r.h));
+            XftDrawDestroy(d);
+
+            res
+        }
+    }
+
+    /// Draw text across the full width of `r`, spreading the whitespace separated words of
+    /// `txt` out so that the first starts at the left edge and the last ends at the right.
+    ///
+    /// Text with fewer than two words, or that does not fit within `r` when separated by
+    /// single spaces, is drawn left aligned as it would be by [Draw::draw_text].
+    pub fn draw_text_justified(&mut self, txt: &str, r: Rect, invert: bool) -> Result<()> {
+        self.assert_thread();
+        let words: Vec<&str> = txt.split_whitespace().collect();
+        if words.len() < 2 {
+            return self.draw_text(txt, 0, r, invert);
+        }
+
+        let mut word_runs = Vec::with_capacity(words.len());
+        for word in words {
+            let runs = self.layout_run(word)?;
+            let (w, _) = runs_extent(&runs);
+            word_runs.push((runs, w as i64));
+        }
+
+        let gaps = word_runs.len() as i64 - 1;
+        let ink: i64 = word_runs.iter().map(|(_, w)| w).sum();
+        let natural = ink + gaps * self.space_width()? as i64;
+        if natural > r.w as i64 {
+            return self.draw_text(txt, 0, r, invert);
+        }
+
+        let r = self.translate_rect(r);
+        self.ensure_fits(r)?;
+        self.fill_text_background(r, invert);
+
+        // Any space that can't be split evenly goes to the leftmost gaps a pixel at a time
+        let extra = r.w as i64 - ink;
+        let mut positioned = Vec::new();
+        let mut x = 0;
+        for (i, (runs, w)) in word_runs.into_iter().enumerate() {
+            positioned.extend(runs.into_iter().map(|run| PositionedGlyphRun {
+                x: x as i32 + run.x,
+                ..run
+            }));
+            let i = i as i64;
+            x += w + extra / gaps + (i < extra % gaps) as i64;
+        }
+
+        self.draw_positioned_runs(&positioned, 0, r, invert)
+    }
+
+    /// Draw each `(txt, r, lpad, invert)` label in the same way as [Draw::draw_text], sharing
+    /// the Xft state needed for rendering between all of them.
+    ///
+    /// All backgrounds are filled before any text is drawn so labels should not overlap.
+    pub fn draw_texts(&mut self, items: &[(String, Rect, u32, bool)]) -> Result<()> {
+        self.assert_thread();
+        let mut labels = Vec::with_capacity(items.len());
+        for (txt, r, lpad, invert) in items {
+            if txt.is_empty() {
+                continue;
+            }
+
+            let r = self.translate_rect(*r);
+            self.ensure_fits(r)?;
+            self.fill_text_background(r, *invert);
+            labels.push((self.layout_run(txt)?, r, *lpad, *invert));
+        }
+
+        unsafe {
+            let d = XftDrawCreate(self.dpy, self.drawable, self.vis.visual, self.vis.colormap);
+            self.clip_xft_draw(d);
+
+            let scheme = self.scheme();
+            let res = labels.iter().try_for_each(|(runs, r, lpad, invert)| {
+                let color = if *invert { &scheme.bg } else { &scheme.fg };
+                let Rect { x, y, h, .. } = *r;
+
+                self.draw_runs(d, color.as_xft_color(), runs, (x + *lpad as i32, y), h)
+            });
+            XftDrawDestroy(d);
+
+            res
+        }
+    }
+
+    /// Draw `txt` in the same way as [Draw::draw_text] but only fill the background behind
+    /// the text itself (grown by `pad` pixels on each side and clipped to `r`) rather than
+    /// the whole of `r`, giving a highlighter style effect.
+    pub fn draw_text_tight_bg(
+        &mut self,
+        txt: &str,
+        lpad: u32,
+        r: Rect,
+        pad: u32,
+        invert: bool,
+    ) -> Result<()> {
+        self.assert_thread();
+        if txt.is_empty() {
+            return Ok(());
+        }
+
+        let r = self.translate_rect(r);
+        self.ensure_fits(r)?;
+        let runs = self.layout_run(txt)?;
+        let (w, h) = runs_extent(&runs);
+
+        let (pad, lpad_i) = (pad as i32, lpad as i32);
+        let x0 = (r.x + lpad_i - pad).max(r.x);
+        let y0 = (r.y + (r.h as i32 - h) / 2 - pad).max(r.y);
+        let x1 = (r.x + lpad_i + w + pad).min(r.x + r.w as i32);
+        let y1 = (r.y + (r.h as i32 + h) / 2 + pad).min(r.y + r.h as i32);
+        if x1 > x0 && y1 > y0 {
+            let bg = Rect {
+                x: x0,
+                y: y0,
+                w: (x1 - x0) as u32,
+                h: (y1 - y0) as u32,
+            };
+            self.fill_text_background(bg, invert);
+        }
+
+        self.draw_positioned_runs(&runs, lpad, r, invert)
+    }
+
+    /// Draw `txt` in the same way as [Draw::draw_text] but render all of it using the loaded
+    /// font at `font_index` (see [GlyphRun::font_index]) rather than matching a font for each
+    /// character. Characters missing from that font are drawn as the font's missing glyph.
+    pub fn draw_text_in_font(
+        &mut self,
+        txt: &str,
+        font_index: usize,
+        lpad: u32,
+        r: Rect,
+        invert: bool,
+    ) -> Result<()> {
+        self.assert_thread();
+        let fm = self.fs.font_match(font_index)?;
+        if txt.is_empty() {
+            return Ok(());
+        }
+
+        let r = self.translate_rect(r);
+        self.ensure_fits(r)?;
+        self.fill_text_background(r, invert);
+        let runs = self.layout_run_in_font(txt, fm)?;
+
+        self.draw_positioned_runs(&runs, lpad, r, invert)
+    }
+
+    /// The [Draw::text_extent] of `txt` when rendered using [Draw::draw_text_in_font].
+    pub fn text_extent_in_font(&mut self, txt: &str, font_index: usize) -> Result<(i32, i32)> {
+        self.assert_thread();
+        let fm = self.fs.font_match(font_index)?;
+        if txt.is_empty() {
+            return Ok((0, 0));
+        }
+
+        let runs = self.layout_run_in_font(txt, fm)?;
+
+        Ok(runs_extent(&runs))
+    }
+
+    // Draw runs in the colors of the active scheme within a rect that has already had the
+    // current translation applied
+    fn draw_positioned_runs(
+        &self,
+        runs: &[PositionedGlyphRun<'_>],
+        lpad: u32,
+        r: Rect,
+        invert: bool,
+    ) -> Result<()> {
+        unsafe {
+            let d = XftDrawCreate(self.dpy, self.drawable, self.vis.visual, self.vis.colormap);
+            self.clip_xft_draw(d);
+
+            let scheme = self.scheme();
+            let color = if invert { &scheme.bg } else { &scheme.fg };
+            let Rect { x, y, h, .. } = r;
+
+            let res = self.draw_runs(d, color.as_xft_color(), runs, (x + lpad as i32, y), h);
+            XftDrawDestroy(d);
+
+            res
+        }
+    }
+
+    // Fill the background behind text in a rect that has already had the current
+    // translation applied
+    fn fill_text_background(&mut self, r: Rect, invert: bool) {
+        if invert {
+            self.fill(r, false); // the inverted background is the scheme fg
+        } else if self.transparent_text_bg {
+            self.fill_transparent(r);
+        } else {
+            self.fill_background(r);
+        }
+    }
+
+    /// The height (ascent + descent) of the primary font, independent of the characters that
+    /// are being drawn.
+    pub fn line_height(&self) -> i32 {
+        self.fs.primary().h
+    }
+
+    /// The advance width of a single cell when treating the primary font as a fixed grid.
+    ///
+    /// This is measured using 'M' so it is only meaningful for monospace fonts.
+    pub fn cell_width(&mut self) -> Result<i32> {
+        self.assert_thread();
+        let (w, _) = self.fs.primary().get_exts(self.dpy, "M")?;
+
+        Ok(w)
+    }
+
+    /// Whether the primary font is monospaced, in which case [Draw::cell_width] can be used
+    /// to lay text out on a grid.
+    pub fn is_monospace(&mut self) -> bool {
+        self.assert_thread();
+        self.fs.is_monospace()
+    }
+
+    /// Draw text positioned on a character grid of [Draw::cell_width] columns and primary
+    /// font height rows, relative to the top left corner of `origin`.
+    pub fn draw_text_at_cell(&mut self, txt: &str, col: u32, row: u32, origin: Rect) -> Result<()> {
+        let cw = self.cell_width()?;
+        let ch = self.fs.primary().h;
+        let n_chars = txt.chars().count() as i32;
+
+        let r = Rect {
+            x: origin.x + col as i32 * cw,
+            y: origin.y + row as i32 * ch,
+            w: (n_chars * cw) as u32,
+            h: ch as u32,
+        };
+
+        self.draw_text(txt, 0, r, false)
+    }
+
+    /// Draw text within `r`, reducing the font size in half point steps (down to a minimum of
+    /// `min_pt`) until the text fits. If it still does not fit at `min_pt` (or the font can't
+    /// be resized, as for core X11 fonts) the text is truncated with an ellipsis.
+    ///
+    /// Smaller sizes are opened as temporary fonts so the current font is left untouched.
+    pub fn draw_text_fit(&mut self, txt: &str, r: Rect, min_pt: f64, invert: bool) -> Result<()> {
+        let (w, _) = self.text_extent(txt)?;
+        if w <= r.w as i32 {
+            return self.draw_text(txt, 0, r, invert);
+        }
+
+        let opts = self.fs.opts();
+        let mut size = self.fs.primary().size();
+        let mut smaller = None;
+
+        while let Some(pt) = size.map(|pt| pt - 0.5).filter(|&pt| pt >= min_pt) {
+            size = Some(pt);
+            let mut fs = self
+                .fs
+                .reload(self.fs.name(), FontOptions { size, ..opts })?;
+            let (w, _) = self.with_fontset(&mut fs, |d| d.text_extent(txt))?;
+            smaller = Some(fs);
+            if w <= r.w as i32 {
+                break;
+            }
+        }
+
+        match smaller {
+            Some(mut fs) => self.with_fontset(&mut fs, |d| d.draw_text_or_ellipsis(txt, r, invert)),
+            None => self.draw_text_or_ellipsis(txt, r, invert),
+        }
+    }
+
+    // Run f using fs in place of the current fonts, which are put back unchanged afterwards
+    fn with_fontset<T>(&mut self, fs: &mut Fontset, f: impl FnOnce(&mut Self) -> T) -> T {
+        std::mem::swap(&mut self.fs, fs);
+        let res = f(self);
+        std::mem::swap(&mut self.fs, fs);
+
+        res
+    }
+
+    // Draw txt if it fits within r, otherwise the longest prefix that fits with an ellipsis
+    fn draw_text_or_ellipsis(&mut self, txt: &str, r: Rect, invert: bool) -> Result<()> {
+        let (w, _) = self.text_extent(txt)?;
+        if w <= r.w as i32 {
+            return self.draw_text(txt, 0, r, invert);
+        }
+        let truncated = self.ellipsize(txt, r.w)?;
+
+        self.draw_text(&truncated, 0, r, invert)
+    }
+
+    // Truncate txt to the longest prefix that fits within max_w once an ellipsis is appended
+    fn ellipsize(&mut self, txt: &str, max_w: u32) -> Result<String> {
+        let mut boundaries: Vec<usize> = txt.char_indices().map(|(i, _)| i).collect();
+
+        while let Some(ix) = boundaries.pop() {
+            let candidate = format!("{}…", &txt[..ix]);
+            let (w, _) = self.text_extent(&candidate)?;
+            if w <= max_w as i32 {
+                return Ok(candidate);
+            }
+        }
+
+        Ok(String::new())
+    }
+
+    /// Draw as much of `txt` as fits within `r` in the same way as [Draw::draw_text],
+    /// returning the byte index of the first character that didn't fit (or `txt.len()` if
+    /// all of the text was drawn) so that the remainder can be drawn later.
+    pub fn draw_text_partial(
+        &mut self,
+        txt: &str,
+        lpad: u32,
+        r: Rect,
+        invert: bool,
+    ) -> Result<usize> {
+        self.assert_thread();
+        let max_w = r.w.saturating_sub(lpad).min(i32::MAX as u32) as i32;
+        let split = self.fitting_prefix_len(txt, max_w)?;
+        self.draw_text(&txt[..split], lpad, r, invert)?;
+
+        Ok(split)
+    }
+
+    /// The number of leading characters of `txt` whose combined advance is no wider than
+    /// `max_w`. Unlike [Draw::draw_text_partial] this counts chars rather than bytes.
+    pub fn chars_that_fit(&mut self, txt: &str, max_w: u32) -> Result<usize> {
+        self.assert_thread();
+        let split = self.fitting_prefix_len(txt, max_w.min(i32::MAX as u32) as i32)?;
+
+        Ok(txt[..split].chars().count())
+    }
+
+    // The length in bytes of the longest prefix of txt that is no wider than max_w
+    fn fitting_prefix_len(&mut self, txt: &str, max_w: i32) -> Result<usize> {
+        let runs = self.layout_run(txt)?;
+        let mut offset = 0;
+
+        for run in runs {
+            if run.x + run.w <= max_w {
+                offset += run.txt.len();
+                continue;
+            }
+
+            for (i, c) in run.txt.char_indices() {
+                let w = self.run_prefix_width(&run, i + c.len_utf8());
+                if run.x + w > max_w {
+                    return Ok(offset + i);
+                }
+            }
+
+            // Rounding in the run extent can leave the whole run just fitting
+            offset += run.txt.len();
+        }
+
+        Ok(offset)
+    }
+
+    /// The x offset in pixels (relative to the start of the text) of the boundary before the
+    /// character at `char_index`. Passing the number of characters in `txt` gives the offset
+    /// of the end of the text.
+    pub fn caret_x(&mut self, txt: &str, char_index: usize) -> Result<i32> {
+        let byte_index = txt
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(txt.len()))
+            .nth(char_index)
+            .ok_or_else(|| Error::CharIndexOutOfBounds {
+                index: char_index,
+                len: txt.chars().count(),
+            })?;
+
+        let (w, _) = self.text_extent(&txt[..byte_index])?;
+
+        Ok(w)
+    }
+
+    /// The index of the character of `txt` (drawn with [Draw::draw_text] using `lpad`) whose
+    /// cell contains `click_x`, relative to the left edge of the rect being drawn into.
+    ///
+    /// Returns `None` if `click_x` falls in the padding before the text or past its end.
+    pub fn char_at_x(&mut self, txt: &str, lpad: u32, click_x: i32) -> Result<Option<usize>> {
+        self.assert_thread();
+        let x = click_x - lpad as i32;
+        if x < 0 {
+            return Ok(None);
+        }
+
+        let runs = self.layout_run(txt)?;
+        let mut char_offset = 0;
+
+        for run in runs {
+            if x >= run.x + run.w {
+                char_offset += run.txt.chars().count();
+                continue;
+            }
+
+            for (n, (i, c)) in run.txt.char_indices().enumerate() {
+                let w = self.run_prefix_width(&run, i + c.len_utf8());
+                if x < run.x + w {
+                    return Ok(Some(char_offset + n));
+                }
+            }
+
+            // Rounding in the run extent can leave x just past the last char of the run
+            char_offset += run.txt.chars().count();
+        }
+
+        Ok(None)
+    }
+
+    /// The width and height of `txt` as it would be rendered by [Draw::draw_text]. The height
+    /// is that of the tallest font needed to render the text.
+    pub fn text_extent(&mut self, txt: &str) -> Result<(i32, i32)> {
+        self.assert_thread();
+        if txt.is_empty() {
+            return Ok((0, 0));
+        }
+
+        let runs = self.layout_run(txt)?;
+
+        Ok(runs_extent(&runs))
+    }
+
+    /// Load any fallback fonts and rasterize the glyphs needed to render `txt` without
+    /// drawing anything, so that the first frame that actually draws it isn't slowed down
+    /// by doing so.
+    pub fn warm_glyphs(&mut self, txt: &str) -> Result<()> {
+        self.assert_thread();
+        // Measuring with Xft loads (and uploads to the server) any glyphs that are missing
+        // from the font's cache
+        self.layout_run(txt)?;
+
+        Ok(())
+    }
+
+    /// The [Draw::text_extent] of each of the given strings, in order.
+    ///
+    /// This is cheaper than calling [Draw::text_extent] for each string as the work that
+    /// doesn't depend on the individual strings is only done once for the whole batch.
+    pub fn text_extent_many(&mut self, strs: &[&str]) -> Result<Vec<(i32, i32)>> {
+        self.assert_thread();
+        // None of the strings contain a nul byte if their concatenation doesn't
+        let joined = strs.concat();
+        let tab_stop = self.tab_stop(&joined)?;
+        CString::new(joined)?;
+
+        let (mut chunks, mut runs) = (Vec::new(), Vec::new());
+        let mut extents = Vec::with_capacity(strs.len());
+        for s in strs {
+            chunks.clear();
+            runs.clear();
+            self.fs.extend_font_chunks(s, &mut chunks);
+            self.position_chunks(&chunks, tab_stop, &mut runs);
+            extents.push(runs_extent(&runs));
+        }
+
+        Ok(extents)
+    }
+
+    /// Copy the given region of the drawable to the same position in a window.
+    ///
+    /// Returns [Error::FlushOutOfBounds] if the region is not contained within the drawable
+    /// and [Error::FlushTargetInvalid] if the window no longer exists.
+    pub fn flush_to(&mut self, win: u32, r: Rect) -> Result<()> {
+        self.flush_to_all(&[win], r)
+    }
+
+    /// Copy the given region of the drawable to the same position on the root window, for
+    /// drawing directly onto the desktop.
+    ///
+    /// Anything drawn this way is lost as soon as the root window is redrawn (for example
+    /// when the wallpaper is set again) so it needs to be flushed again in response to
+    /// Expose events on the root. Compositors and desktop environments often cover the root
+    /// window with a window or root pixmap of their own, in which case nothing flushed here
+    /// will be visible. Pseudo-transparency is not handled: the region is copied as is.
+    pub fn flush_to_root(&mut self, r: Rect) -> Result<()> {
+        self.flush_to(self.root as u32, r)
+    }
+
+    /// Copy the same region of the drawable to each of the given windows, syncing with the
+    /// server once after all of the copies have been issued.
+    ///
+    /// If any of the windows no longer exist then the others are still flushed before
+    /// returning [Error::FlushTargetInvalid] for the first of them.
+    pub fn flush_to_all(&mut self, wins: &[u32], r: Rect) -> Result<()> {
+        self.flush_windows(wins, r, false)
+    }
+
+    /// Flush in the same way as [Draw::flush_to], returning once the server has processed
+    /// the copy. When `discard_events` is true any events already queued for this connection
+    /// (such as a backlog of Expose events from rapid redraws) are dropped.
+    pub fn flush_to_sync(&mut self, win: u32, r: Rect, discard_events: bool) -> Result<()> {
+        self.flush_windows(&[win], r, discard_events)
+    }
+
+    /// Copy the given region of the drawable to a window in the same way as [Draw::flush_to]
+    /// but without waiting for the server to process the copy.
+    ///
+    /// This avoids a round trip per call when flushing many small regions each frame, but
+    /// the copies (and any errors they cause) may not reach the server until the next call
+    /// to [Draw::sync] or anything else that syncs or flushes the connection. Errors from the
+    /// copy are reported by the default Xlib error handler rather than being logged.
+    pub fn flush_no_sync(&mut self, win: u32, r: Rect) -> Result<()> {
+        self.assert_thread();
+        self.check_flush_bounds(r)?;
+        let Rect { x, y, w, h } = r;
+
+        self.suspend_clip();
+        unsafe {
+            XCopyArea(
+                self.dpy,
+                self.drawable,
+                win as Window,
+                self.gc,
+                x,
+                y,
+                w,
+                h,
+                x,
+                y,
+            )
+        };
+        self.resume_clip();
+
+        Ok(())
+    }
+
+    /// Wait for the server to process all requests made so far, such as those issued by
+    /// [Draw::flush_no_sync].
+    pub fn sync(&self) {
+        self.assert_thread();
+        unsafe { XSync(self.dpy, False) };
+    }
+
+    fn check_flush_bounds(&self, r: Rect) -> Result<()> {
+        let Rect { x, y, w, h } = r;
+        let fits = x >= 0
+            && y >= 0
+            && x as u64 + w as u64 <= self.w as u64
+            && y as u64 + h as u64 <= self.h as u64;
+        if !fits {
+            return Err(Error::FlushOutOfBounds {
+                r,
+                w: self.w,
+                h: self.h,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn flush_windows(&mut self, wins: &[u32], r: Rect, discard_events: bool) -> Result<()> {
+        self.assert_thread();
+        self.check_flush_bounds(r)?;
+        let Rect { x, y, w, h } = r;
+
+        self.suspend_clip();
+        let (_, err) = unsafe {
+            trap_errors(self.dpy, || {
+                for &win in wins {
+                    let win = win as Window;
+                    XCopyArea(self.dpy, self.drawable, win, self.gc, x, y, w, h, x, y);
+                }
+            })
+        };
+        self.resume_clip();
+
+        if discard_events {
+            unsafe { XSync(self.dpy, True) };
+        }
+
+        log_debug!("flushed {r:?} to {wins:?}");
+        match err {
+            Some(e) if e.error_code == BadWindow || e.error_code == BadDrawable => {
+                Err(Error::FlushTargetInvalid(e.resource_id as u32))
+            }
+            Some(e) => {
+                log_error!("{}", Error::X(e));
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Free the X resources held by this Draw and close its connection to the X server now
+    /// rather than whenever it is dropped, waiting for the server to process the requests
+    /// before returning.
+    pub fn close(mut self) {
+        self.assert_thread();
+        self.free_resources();
+    }
+
+    // Idempotent so that Drop is a no-op following an explicit close
+    fn free_resources(&mut self) {
+        if self.closed {
+            return;
+        }
+
+        self.free_layers();
+        self.layers.clear();
+        self.free_clips();
+        unsafe {
+            if self.owns_drawable {
+                XFreePixmap(self.dpy, self.drawable);
+            }
+            XFreeGC(self.dpy, self.gc);
+        }
+        self.free_stipple();
+        self.clear_background_image();
+
+        // Colors need to be freed before the colormap they were allocated from
+        self.schemes.clear();
+        self.colors.clear();
+        self.fs.close();
+        unsafe {
+            self.vis.free(self.dpy);
+            XCloseDisplay(self.dpy);
+        }
+        self.closed = true;
+    }
+
+    // A Draw holds a single Xlib connection which is not safe to use from multiple threads.
+    // Draw is !Send so this can only happen if a caller has worked around that using unsafe
+    // code but when it does it corrupts the protocol stream and results in seemingly random
+    // X errors so we check for it in debug builds.
+    #[inline]
+    fn assert_thread(&self) {
+        #[cfg(debug_assertions)]
+        debug_assert_eq!(
+            self.thread,
+            std::thread::current().id(),
+            "Draw used from a thread other than the one it was created on"
+        );
+    }
+
+    /// The underlying Display connection, Drawable and GC used by this [Draw] for making
+    /// X calls that are not wrapped by this crate.
+    ///
+    /// # Safety
+    /// All three resources remain owned by this [Draw] and are freed when it is dropped.
+    /// Callers must not free or close any of them and must not use them after the [Draw]
+    /// has been dropped.
+    pub unsafe fn raw_parts(&self) -> (*mut Display, Drawable, GC) {
+        (self.dpy, self.drawable, self.gc)
+    }
+
+    /// The pixmap that this [Draw] renders into, for use as the source of X calls (such as
+    /// XCopyArea) that are not wrapped by this crate. It is replaced by [Draw::resize].
+    pub fn drawable(&self) -> Drawable {
+        self.drawable
+    }
+
+    /// The Visual, Colormap and depth used by this [Draw], for creating windows that are
+    /// compatible with the drawable.
+    pub fn raw_visual(&self) -> (*mut Visual, Colormap, i32) {
+        (self.vis.visual, self.vis.colormap, self.vis.depth)
+    }
+}
+
+fn open_display() -> Result<*mut Display> {
+    let dpy = unsafe { XOpenDisplay(std::ptr::null()) };
+    if dpy.is_null() {
+        log_error!("XOpenDisplay failed");
+        return Err(Error::UnableToOpenDisplay);
+    }
+    log_debug!("opened display {dpy:?}");
+
+    Ok(dpy)
+}
+
+unsafe fn create_drawable(
+    dpy: *mut Display,
+    root: Window,
+    vis: VisualInfo,
+    w: u32,
+    h: u32,
+) -> (Drawable, GC) {
+    let drawable = XCreatePixmap(dpy, root, w, h, vis.depth as u32);
+    log_debug!("created {w}x{h} pixmap {drawable} with depth {}", vis.depth);
+
+    (drawable, create_gc(dpy, drawable))
+}
+
+// The GC needs to be created against a drawable of the depth it will be used with
+unsafe fn create_gc(dpy: *mut Display, drawable: Drawable) -> GC {
+    let gc = XCreateGC(dpy, drawable, 0, std::ptr::null_mut());
+    set_default_gc_state(dpy, gc);
+
+    gc
+}
+
+impl Drop for Draw {
+    fn drop(&mut self) {
+        self.free_resources();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: i32, y: i32, w: u32, h: u32) -> Rect {
+        Rect { x, y, w, h }
+    }
+
+    #[test]
+    fn clamped_to_trims_negative_origin() {
+        let r = rect(-5, -10, 20, 30);
+
+        assert_eq!(r.clamped_to(100, 100), Some(rect(0, 0, 15, 20)));
+    }
+
+    #[test]
+    fn clamped_to_trims_overhang_on_the_far_edges() {
+        let r = rect(90, 95, 20, 20);
+
+        assert_eq!(r.clamped_to(100, 100), Some(rect(90, 95, 10, 5)));
+    }
+
+    #[test]
+    fn clamped_to_returns_none_outside_the_drawable() {
+        for r in [
+            rect(-20, 0, 20, 10), // ends exactly at the left edge
+            rect(100, 0, 10, 10), // starts exactly at the right edge
+            rect(0, 150, 10, 10), // below
+            rect(10, 10, 0, 10),  // zero width
+        ] {
+            assert_eq!(r.clamped_to(100, 100), None, "{r:?}");
+        }
+    }
+
+    #[test]
+    fn clamped_to_handles_far_off_coordinates() {
+        // Truncated to 16 bits -70_000 would wrap around to -4464 and 70_000 to 4464
+        assert_eq!(rect(-70_000, 0, 10, 10).clamped_to(5000, 5000), None);
+        assert_eq!(rect(70_000, 0, 10, 10).clamped_to(5000, 5000), None);
+        assert_eq!(
+            rect(-70_000, 0, 70_010, 10).clamped_to(5000, 5000),
+            Some(rect(0, 0, 10, 10))
+        );
+        assert_eq!(
+            rect(i32::MAX, i32::MAX, u32::MAX, u32::MAX).clamped_to(100, 100),
+            None
+        );
+    }
+
+    #[test]
+    fn split_at_large_offsets_saturates() {
+        let r = rect(0, 0, u32::MAX, u32::MAX);
+        let (left, right) = r.split_at_x(3_000_000_000);
+        let (top, bottom) = r.split_at_y(3_000_000_000);
+
+        assert_eq!(
+            (left.w, right.x, right.w),
+            (3_000_000_000, i32::MAX, 1_294_967_295)
+        );
+        assert_eq!(
+            (top.h, bottom.y, bottom.h),
+            (3_000_000_000, i32::MAX, 1_294_967_295)
+        );
+    }
+
+    #[test]
+    fn as_xrectangle_saturates() {
+        let xr = rect(-70_000, 70_000, 100_000, u32::MAX).as_xrectangle();
+
+        assert_eq!((xr.x, xr.y), (i16::MIN, i16::MAX));
+        assert_eq!((xr.width, xr.height), (u16::MAX, u16::MAX));
+    }
+
+    #[test]
+    fn as_xrectangle_keeps_in_range_values() {
+        let xr = rect(-12, 34, 56, 78).as_xrectangle();
+
+        assert_eq!((xr.x, xr.y, xr.width, xr.height), (-12, 34, 56, 78));
     }
 }
@@ -0,0 +1,20 @@
+//! Optional logging of X operations
+//!
+//! With the "log" feature disabled debug events are compiled out entirely and errors are
+//! printed to stdout.
+
+macro_rules! log_debug {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "log")]
+        ::log::debug!($($arg)*);
+    };
+}
+
+macro_rules! log_error {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "log")]
+        ::log::error!($($arg)*);
+        #[cfg(not(feature = "log"))]
+        println!("ERROR: {}", format_args!($($arg)*));
+    };
+}
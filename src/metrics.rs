@@ -0,0 +1,59 @@
+//! Precomputed glyph advances for measuring text away from the X connection
+use crate::{Draw, Result};
+use std::collections::HashMap;
+
+/// The advance widths of a fixed set of characters as they would be rendered by a [Draw],
+/// created using [Draw::export_metrics].
+///
+/// Metrics do not hold on to the connection so they can be sent to and used from other
+/// threads. Text containing characters that were not exported needs to be measured using
+/// [Draw::text_extent] instead.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Metrics {
+    advances: HashMap<char, i32>,
+    height: i32,
+}
+
+impl Metrics {
+    /// The advance width of `txt`, counting any characters that were not exported as zero.
+    pub fn measure(&self, txt: &str) -> i32 {
+        txt.chars().filter_map(|c| self.advances.get(&c)).sum()
+    }
+
+    /// The advance width of `txt` if every character in it was exported.
+    pub fn try_measure(&self, txt: &str) -> Option<i32> {
+        txt.chars().map(|c| self.advances.get(&c)).sum()
+    }
+
+    /// The advance width of a single character if it was exported.
+    pub fn advance(&self, c: char) -> Option<i32> {
+        self.advances.get(&c).copied()
+    }
+
+    /// The height of the primary font at the point these metrics were exported.
+    pub fn line_height(&self) -> i32 {
+        self.height
+    }
+}
+
+impl Draw {
+    /// Measure each of the given characters using the current font (including any fallback
+    /// fonts needed to render them) so that text made up of them can be measured without
+    /// access to the connection.
+    ///
+    /// Metrics are not updated if the font is changed later on.
+    pub fn export_metrics(&mut self, chars: &[char]) -> Result<Metrics> {
+        self.assert_thread();
+        let mut advances = HashMap::with_capacity(chars.len());
+        let mut buf = [0; 4];
+        for &c in chars {
+            let (w, _) = self.text_extent(c.encode_utf8(&mut buf))?;
+            advances.insert(c, w);
+        }
+
+        Ok(Metrics {
+            advances,
+            height: self.line_height(),
+        })
+    }
+}
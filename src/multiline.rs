@@ -0,0 +1,89 @@
+//! Wrapping and drawing text over multiple lines
+use crate::{Draw, Rect, Result};
+
+impl Draw {
+    /// Set the distance between the top of consecutive lines drawn by [Draw::draw_text_lines]
+    /// as a multiple of [Draw::line_height]. Defaults to 1.0.
+    pub fn set_line_spacing(&mut self, multiplier: f64) {
+        self.line_spacing = multiplier.max(0.0);
+    }
+
+    /// The distance in pixels between the top of consecutive lines.
+    pub fn line_advance(&self) -> i32 {
+        (self.line_height() as f64 * self.line_spacing).round() as i32
+    }
+
+    /// Split `txt` into lines no wider than `max_w`, breaking at whitespace where possible and
+    /// always breaking at newlines. Words that are too long to fit on a line of their own are
+    /// broken between characters.
+    pub fn wrap_text<'a>(&mut self, txt: &'a str, max_w: u32) -> Result<Vec<&'a str>> {
+        self.assert_thread();
+        let mut lines = Vec::new();
+
+        for para in txt.split('\n') {
+            let mut rest = para;
+
+            loop {
+                let fit = self.fitting_prefix_len(rest, max_w as i32)?;
+                if fit == rest.len() {
+                    lines.push(rest);
+                    break;
+                }
+
+                let split = if rest[fit..].starts_with(char::is_whitespace) {
+                    fit
+                } else {
+                    match rest[..fit].rfind(char::is_whitespace) {
+                        Some(ix) if ix > 0 => ix,
+                        // Always make progress, even if not a single character fits
+                        _ if fit == 0 => rest.chars().next().map(char::len_utf8).unwrap_or(0),
+                        _ => fit,
+                    }
+                };
+
+                lines.push(rest[..split].trim_end());
+                rest = rest[split..].trim_start();
+                if rest.is_empty() {
+                    break;
+                }
+            }
+        }
+
+        Ok(lines)
+    }
+
+    /// Draw each line in turn from the top of `r`, [Draw::line_advance] pixels apart. The
+    /// background of the whole of `r` is filled first and any lines that would extend past
+    /// the bottom of `r` are not drawn.
+    pub fn draw_text_lines(
+        &mut self,
+        lines: &[&str],
+        lpad: u32,
+        r: Rect,
+        invert: bool,
+    ) -> Result<()> {
+        self.assert_thread();
+        let translated = self.translate_rect(r);
+        self.ensure_fits(translated)?;
+        self.fill_text_background(translated, invert);
+
+        let (line_h, advance) = (self.line_height(), self.line_advance());
+        let bottom = r.y as i64 + r.h as i64;
+
+        for (i, line) in lines.iter().enumerate() {
+            let y = r.y + i as i32 * advance;
+            if y as i64 + line_h as i64 > bottom {
+                break;
+            }
+
+            let row = Rect {
+                y,
+                h: line_h as u32,
+                ..r
+            };
+            self.draw_text(line, lpad, row, invert)?;
+        }
+
+        Ok(())
+    }
+}
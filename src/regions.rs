@@ -0,0 +1,27 @@
+//! Named regions of the drawable for mapping clicks back to what was drawn there
+use crate::{Draw, Rect};
+
+impl Draw {
+    /// Register `r` (with the current translation applied) under `name` so that points
+    /// within it can be found using [Draw::region_at]. Registering a name again replaces
+    /// its previous region.
+    pub fn register_region(&mut self, name: &str, r: Rect) {
+        let r = self.translate_rect(r);
+        self.regions.retain(|(n, _)| n != name);
+        self.regions.push((name.to_string(), r));
+    }
+
+    /// Remove all registered regions.
+    pub fn clear_regions(&mut self) {
+        self.regions.clear();
+    }
+
+    /// The name of the most recently registered region containing the point (x, y), if any.
+    pub fn region_at(&self, x: i32, y: i32) -> Option<&str> {
+        self.regions
+            .iter()
+            .rev()
+            .find(|(_, r)| r.contains(x, y))
+            .map(|(name, _)| name.as_str())
+    }
+}
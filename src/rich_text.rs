@@ -0,0 +1,59 @@
+//! Drawing a single line of text made up of differently colored segments
+use crate::{layout::runs_extent, Align, Draw, Rect, Result};
+use x11::{
+    xft::{XftDrawCreate, XftDrawDestroy, XftDrawSetClip},
+    xlib::XDestroyRegion,
+};
+
+impl Draw {
+    /// Draw a line of text made up of `(text, colorscheme name)` segments, each rendered in
+    /// the foreground color of its colorscheme. The background of the whole of `r` is filled
+    /// once using the active colorscheme before any text is drawn, or left transparent as
+    /// for [Draw::draw_text] (see [Draw::set_transparent_text_background]).
+    ///
+    /// The segments are aligned as a whole within `r` with `lpad` padding on either side.
+    /// If they are too wide to fit they are left aligned and clipped to `r`.
+    pub fn draw_rich_text(
+        &mut self,
+        runs: &[(&str, &str)],
+        lpad: u32,
+        r: Rect,
+        align: Align,
+    ) -> Result<()> {
+        self.assert_thread();
+        let r = self.translate_rect(r);
+        self.ensure_fits(r)?;
+
+        // Resolve every scheme before drawing so an unknown name doesn't leave a partial line
+        let mut segments = Vec::with_capacity(runs.len());
+        for &(txt, scheme) in runs {
+            let ix = self.scheme_index(scheme)?;
+            segments.push((self.layout_run(txt)?, ix));
+        }
+
+        let total_w: i32 = segments.iter().map(|(s, _)| runs_extent(s).0).sum();
+        let available = r.w.saturating_sub(2 * lpad);
+        let mut x = r.x + lpad as i32 + align.offset(total_w, available);
+
+        self.fill_text_background(r, false);
+
+        unsafe {
+            let d = XftDrawCreate(self.dpy, self.drawable, self.vis.visual, self.vis.colormap);
+            let clip = self.clip_region(r);
+            XftDrawSetClip(d, clip);
+            XDestroyRegion(clip);
+
+            let res = segments.iter().try_for_each(|(laid_out, ix)| {
+                let color = self.schemes[*ix].fg.as_xft_color();
+                self.draw_runs(d, color, laid_out, (x, r.y), r.h)?;
+                x += runs_extent(laid_out).0;
+
+                Ok(())
+            });
+
+            XftDrawDestroy(d);
+
+            res
+        }
+    }
+}
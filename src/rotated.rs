@@ -0,0 +1,93 @@
+//! Drawing text rotated by a multiple of 90 degrees
+//!
+//! Text is rendered upright into a temporary pixmap which is then composited into the
+//! drawable using an XRender transform.
+use crate::{Draw, Error, Rect, Result, Rotation};
+use x11::{
+    xft::{XftDrawCreate, XftDrawDestroy},
+    xlib::{FillSolid, XCreatePixmap, XFillRectangle, XFreePixmap, XSetFillStyle, XSetForeground},
+    xrender::{
+        PictOpSrc, XRenderComposite, XRenderCreatePicture, XRenderFindVisualFormat,
+        XRenderFreePicture, XRenderSetPictureTransform, XTransform,
+    },
+};
+
+impl Draw {
+    /// Draw `txt` rotated clockwise by `rotation`, filling the background of `r` in the same
+    /// way as [Draw::draw_text] with a solid color. For [Rotation::R90] and [Rotation::R270]
+    /// the text runs along the height of `r` and is centered across its width.
+    pub fn draw_text_rotated(
+        &mut self,
+        txt: &str,
+        r: Rect,
+        rotation: Rotation,
+        invert: bool,
+    ) -> Result<()> {
+        self.assert_thread();
+        let r = self.translate_rect(r);
+        self.ensure_fits(r)?;
+        if r.w == 0 || r.h == 0 {
+            return Ok(());
+        }
+
+        // The size of the upright text before rotation
+        let (uw, uh) = match rotation {
+            Rotation::R90 | Rotation::R270 => (r.h, r.w),
+            Rotation::R180 => (r.w, r.h),
+        };
+        let runs = self.layout_run(txt)?;
+
+        unsafe {
+            let fmt = XRenderFindVisualFormat(self.dpy, self.vis.visual);
+            if fmt.is_null() {
+                return Err(Error::UnsupportedVisual((*self.vis.visual).class));
+            }
+
+            let pixmap = XCreatePixmap(self.dpy, self.root, uw, uh, self.vis.depth as u32);
+            let scheme = self.scheme();
+            let (fg, bg) = if invert {
+                (&scheme.bg, scheme.fg())
+            } else {
+                (&scheme.fg, scheme.bg())
+            };
+            // The clip applies to the drawable, not the scratch pixmap
+            self.suspend_clip();
+            XSetForeground(self.dpy, self.gc, bg);
+            XSetFillStyle(self.dpy, self.gc, FillSolid);
+            XFillRectangle(self.dpy, pixmap, self.gc, 0, 0, uw, uh);
+            self.restore_fill_style();
+
+            let d = XftDrawCreate(self.dpy, pixmap, self.vis.visual, self.vis.colormap);
+            let res = self.draw_runs(d, fg.as_xft_color(), &runs, (0, 0), uh);
+            XftDrawDestroy(d);
+            self.resume_clip();
+
+            if res.is_ok() {
+                let src = XRenderCreatePicture(self.dpy, pixmap, fmt, 0, std::ptr::null());
+                let dst = XRenderCreatePicture(self.dpy, self.drawable, fmt, 0, std::ptr::null());
+                self.clip_picture(dst);
+
+                // The transform maps points in r back to points in the upright text
+                let one = 1 << 16;
+                let (uw, uh) = (uw as i32 * one, uh as i32 * one);
+                let matrix = match rotation {
+                    Rotation::R90 => [[0, one, 0], [-one, 0, uh], [0, 0, one]],
+                    Rotation::R180 => [[-one, 0, uw], [0, -one, uh], [0, 0, one]],
+                    Rotation::R270 => [[0, -one, uw], [one, 0, 0], [0, 0, one]],
+                };
+                XRenderSetPictureTransform(self.dpy, src, &mut XTransform { matrix });
+
+                XRenderComposite(
+                    self.dpy, PictOpSrc, src, 0, dst, 0, 0, 0, 0, r.x, r.y, r.w, r.h,
+                );
+
+                XRenderFreePicture(self.dpy, src);
+                XRenderFreePicture(self.dpy, dst);
+            }
+
+            XFreePixmap(self.dpy, pixmap);
+
+            res
+        }
+    }
+}
@@ -0,0 +1,44 @@
+//! Monitor geometry via XRandR
+use crate::{Draw, Rect};
+use x11::xrandr::{XRRFreeCrtcInfo, XRRFreeScreenResources, XRRGetCrtcInfo, XRRGetScreenResources};
+
+impl Draw {
+    /// The geometry of each active monitor attached to the root window, in the order they
+    /// are reported by the server.
+    pub fn screens(&self) -> Vec<Rect> {
+        self.assert_thread();
+        let mut rects = Vec::new();
+
+        unsafe {
+            let res = XRRGetScreenResources(self.dpy, self.root);
+            if res.is_null() {
+                return rects;
+            }
+
+            let crtcs = std::slice::from_raw_parts((*res).crtcs, (*res).ncrtc as usize);
+            for &crtc in crtcs {
+                let info = XRRGetCrtcInfo(self.dpy, res, crtc);
+                if info.is_null() {
+                    continue;
+                }
+
+                // Disabled CRTCs are reported with no outputs and a zero sized mode
+                let i = &*info;
+                if i.noutput > 0 && i.width > 0 && i.height > 0 {
+                    rects.push(Rect {
+                        x: i.x,
+                        y: i.y,
+                        w: i.width,
+                        h: i.height,
+                    });
+                }
+
+                XRRFreeCrtcInfo(info);
+            }
+
+            XRRFreeScreenResources(res);
+        }
+
+        rects
+    }
+}
@@ -0,0 +1,123 @@
+//! Filled polygons and the small indicator shapes built on top of them
+use crate::{saturate_i16, Draw, Rect, Result};
+use std::os::raw::c_int;
+use x11::xlib::{Complex, Convex, CoordModeOrigin, XFillPolygon, XPoint, XSetForeground};
+
+/// The direction that a shape drawn by [Draw::draw_chevron] points in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Draw {
+    /// Fill the polygon with the given vertices using the foreground color of the active
+    /// colorscheme. The polygon is closed automatically and may be self-intersecting.
+    pub fn fill_polygon(&mut self, points: &[(i32, i32)], invert: bool) -> Result<()> {
+        self.fill_polygon_shape(points, Complex, invert)
+    }
+
+    /// Fill the triangle with the given corners.
+    pub fn fill_triangle(
+        &mut self,
+        p1: (i32, i32),
+        p2: (i32, i32),
+        p3: (i32, i32),
+        invert: bool,
+    ) -> Result<()> {
+        self.fill_polygon_shape(&[p1, p2, p3], Convex, invert)
+    }
+
+    /// Fill a triangular chevron centered on `center` pointing in the given direction. The
+    /// base of the triangle is `size` pixels long and its height is half of that.
+    pub fn draw_chevron(
+        &mut self,
+        center: (i32, i32),
+        size: u32,
+        dir: Direction,
+        invert: bool,
+    ) -> Result<()> {
+        let (cx, cy) = center;
+        let (half, quarter) = ((size / 2) as i32, (size / 4) as i32);
+
+        let (p1, p2, p3) = match dir {
+            Direction::Up => (
+                (cx - half, cy + quarter),
+                (cx + half, cy + quarter),
+                (cx, cy - quarter),
+            ),
+            Direction::Down => (
+                (cx - half, cy - quarter),
+                (cx + half, cy - quarter),
+                (cx, cy + quarter),
+            ),
+            Direction::Left => (
+                (cx + quarter, cy - half),
+                (cx + quarter, cy + half),
+                (cx - quarter, cy),
+            ),
+            Direction::Right => (
+                (cx - quarter, cy - half),
+                (cx - quarter, cy + half),
+                (cx + quarter, cy),
+            ),
+        };
+
+        self.fill_triangle(p1, p2, p3, invert)
+    }
+
+    fn fill_polygon_shape(
+        &mut self,
+        points: &[(i32, i32)],
+        shape: c_int,
+        invert: bool,
+    ) -> Result<()> {
+        self.assert_thread();
+        if points.len() < 3 {
+            return Ok(());
+        }
+
+        let points: Vec<(i32, i32)> = points.iter().map(|&p| self.translate_point(p)).collect();
+        let (min_x, max_x) = min_max(points.iter().map(|p| p.0));
+        let (min_y, max_y) = min_max(points.iter().map(|p| p.1));
+        self.ensure_fits(Rect {
+            x: min_x,
+            y: min_y,
+            w: min_x.abs_diff(max_x) + 1,
+            h: min_y.abs_diff(max_y) + 1,
+        })?;
+
+        let mut xpoints: Vec<XPoint> = points
+            .iter()
+            .map(|&(x, y)| XPoint {
+                x: saturate_i16(x),
+                y: saturate_i16(y),
+            })
+            .collect();
+
+        let scheme = self.scheme();
+        let pixel = if invert { scheme.bg() } else { scheme.fg() };
+
+        unsafe {
+            XSetForeground(self.dpy, self.gc, pixel);
+            XFillPolygon(
+                self.dpy,
+                self.drawable,
+                self.gc,
+                xpoints.as_mut_ptr(),
+                xpoints.len() as i32,
+                shape,
+                CoordModeOrigin,
+            );
+        }
+
+        Ok(())
+    }
+}
+
+// Callers guarantee that there is at least one value
+fn min_max(vals: impl Iterator<Item = i32>) -> (i32, i32) {
+    vals.fold((i32::MAX, i32::MIN), |(lo, hi), v| (lo.min(v), hi.max(v)))
+}
@@ -0,0 +1,170 @@
+//! Fill and line styles applied to the GC used for drawing
+use crate::{Draw, Error, Rect, Result};
+use std::{
+    mem::MaybeUninit,
+    os::raw::{c_char, c_int, c_ulong},
+};
+use x11::xlib::{
+    CapButt, Display, FillSolid, FillStippled, GCCapStyle, GCJoinStyle, GCLineStyle, GCLineWidth,
+    JoinMiter, LineOnOffDash, LineSolid, XChangeGC, XCreateBitmapFromData, XFreePixmap,
+    XGetGCValues, XSetDashes, XSetFillStyle, XSetLineAttributes, XSetStipple, GC,
+};
+
+// The GC state that a Draw is created with
+pub(crate) unsafe fn set_default_gc_state(dpy: *mut Display, gc: GC) {
+    XSetLineAttributes(dpy, gc, 1, LineSolid, CapButt, JoinMiter);
+    XSetFillStyle(dpy, gc, FillSolid);
+}
+
+/// A 1-bit pattern for use with [FillStyle::Stippled].
+///
+/// Bits are laid out in the same way as XBM data: each row is padded to a whole number of
+/// bytes and the least significant bit of each byte is the leftmost pixel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stipple {
+    w: u32,
+    h: u32,
+    bits: Vec<u8>,
+}
+
+impl Stipple {
+    pub fn try_new(w: u32, h: u32, bits: Vec<u8>) -> Result<Self> {
+        let expected = w.div_ceil(8) as usize * h as usize;
+        if w == 0 || h == 0 || bits.len() != expected {
+            return Err(Error::InvalidStipple { w, h });
+        }
+
+        Ok(Self { w, h, bits })
+    }
+
+    /// A square pattern of diagonal lines spaced `size` pixels apart
+    pub fn diagonal_hatch(size: u32) -> Self {
+        let size = size.max(2);
+        let stride = size.div_ceil(8) as usize;
+        let mut bits = vec![0; stride * size as usize];
+
+        for y in 0..size {
+            let x = size - 1 - y;
+            bits[y as usize * stride + x as usize / 8] |= 1 << (x % 8);
+        }
+
+        Self {
+            w: size,
+            h: size,
+            bits,
+        }
+    }
+}
+
+/// How filled shapes are painted.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum FillStyle {
+    /// Fill the whole shape with the foreground color
+    #[default]
+    Solid,
+    /// Fill only the set bits of the given pattern (tiled across the drawable) with the
+    /// foreground color, leaving the rest of the shape untouched
+    Stippled(Stipple),
+}
+
+impl Draw {
+    /// Set the style used for all subsequent fills, including the background of text.
+    pub fn set_fill_style(&mut self, style: FillStyle) -> Result<()> {
+        self.assert_thread();
+        self.free_stipple();
+
+        unsafe {
+            match style {
+                FillStyle::Solid => {
+                    XSetFillStyle(self.dpy, self.gc, FillSolid);
+                }
+
+                FillStyle::Stippled(Stipple { w, h, bits }) => {
+                    let pixmap = XCreateBitmapFromData(
+                        self.dpy,
+                        self.drawable,
+                        bits.as_ptr() as *const _,
+                        w,
+                        h,
+                    );
+                    if pixmap == 0 {
+                        return Err(Error::InvalidStipple { w, h });
+                    }
+
+                    self.stipple = pixmap;
+                    XSetStipple(self.dpy, self.gc, pixmap);
+                    XSetFillStyle(self.dpy, self.gc, FillStippled);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Draw the outline of a rect using alternating on/off dash lengths (in pixels) in the
+    /// same way as [Draw::draw_rect]. The previous line style is restored afterwards.
+    pub fn draw_rect_dashed(&mut self, r: Rect, dashes: &[u8], invert: bool) -> Result<()> {
+        self.assert_thread();
+        if dashes.is_empty() || dashes.contains(&0) {
+            return Err(Error::InvalidDashes);
+        }
+
+        let mask = (GCLineWidth | GCLineStyle | GCCapStyle | GCJoinStyle) as c_ulong;
+        let mut prev = unsafe {
+            let mut prev = MaybeUninit::zeroed();
+            XGetGCValues(self.dpy, self.gc, mask, prev.as_mut_ptr());
+            let prev = prev.assume_init();
+
+            XSetDashes(
+                self.dpy,
+                self.gc,
+                0,
+                dashes.as_ptr() as *const c_char,
+                dashes.len() as c_int,
+            );
+            XSetLineAttributes(
+                self.dpy,
+                self.gc,
+                prev.line_width as u32,
+                LineOnOffDash,
+                prev.cap_style,
+                prev.join_style,
+            );
+
+            prev
+        };
+
+        let res = self.draw_rect(r, invert);
+        unsafe { XChangeGC(self.dpy, self.gc, mask, &mut prev) };
+
+        res
+    }
+
+    /// Restore the line and fill styles of the GC to the state it was created with: solid
+    /// one pixel lines and solid fills.
+    pub fn reset_gc(&mut self) {
+        self.assert_thread();
+        self.free_stipple();
+
+        unsafe { set_default_gc_state(self.dpy, self.gc) };
+    }
+
+    // Put back whichever fill style was last set with set_fill_style after temporarily
+    // changing it
+    pub(crate) fn restore_fill_style(&self) {
+        let style = if self.stipple != 0 {
+            FillStippled
+        } else {
+            FillSolid
+        };
+
+        unsafe { XSetFillStyle(self.dpy, self.gc, style) };
+    }
+
+    pub(crate) fn free_stipple(&mut self) {
+        if self.stipple != 0 {
+            unsafe { XFreePixmap(self.dpy, self.stipple) };
+            self.stipple = 0;
+        }
+    }
+}
@@ -0,0 +1,82 @@
+//! The visual, colormap and depth that everything is drawn with
+use crate::SCREEN;
+use std::{mem::MaybeUninit, os::raw::c_int};
+use x11::xlib::{
+    AllocNone, Colormap, Display, TrueColor, Visual, VisualClassMask, VisualDepthMask,
+    VisualScreenMask, Window, XCreateColormap, XDefaultColormap, XDefaultDepth, XDefaultVisual,
+    XFree, XFreeColormap, XGetVisualInfo, XVisualInfo,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct VisualInfo {
+    pub(crate) visual: *mut Visual,
+    pub(crate) colormap: Colormap,
+    pub(crate) depth: i32,
+    owns_colormap: bool,
+}
+
+impl VisualInfo {
+    pub(crate) unsafe fn default_for(dpy: *mut Display) -> Self {
+        Self {
+            visual: XDefaultVisual(dpy, SCREEN),
+            colormap: XDefaultColormap(dpy, SCREEN),
+            depth: XDefaultDepth(dpy, SCREEN),
+            owns_colormap: false,
+        }
+    }
+
+    // A 32-bit TrueColor visual with a colormap of its own, if the server has one
+    pub(crate) unsafe fn argb(dpy: *mut Display, root: Window) -> Option<Self> {
+        Self::with_depth(dpy, root, 32)
+    }
+
+    // The default visual if it has the requested depth, otherwise a TrueColor visual of that
+    // depth with a colormap of its own if the server has one
+    pub(crate) unsafe fn for_depth(dpy: *mut Display, root: Window, depth: i32) -> Option<Self> {
+        if depth == XDefaultDepth(dpy, SCREEN) {
+            Some(Self::default_for(dpy))
+        } else {
+            Self::with_depth(dpy, root, depth)
+        }
+    }
+
+    unsafe fn with_depth(dpy: *mut Display, root: Window, depth: i32) -> Option<Self> {
+        let mut template: XVisualInfo = MaybeUninit::zeroed().assume_init();
+        template.screen = SCREEN;
+        template.depth = depth;
+        template.class = TrueColor;
+        let mask = VisualScreenMask | VisualDepthMask | VisualClassMask;
+
+        let mut n: c_int = 0;
+        let infos = XGetVisualInfo(dpy, mask, &mut template, &mut n);
+        if infos.is_null() {
+            return None;
+        }
+
+        let visual = if n > 0 { Some((*infos).visual) } else { None };
+        XFree(infos as *mut _);
+
+        visual.map(|visual| Self {
+            visual,
+            colormap: XCreateColormap(dpy, root, visual, AllocNone),
+            depth,
+            owns_colormap: true,
+        })
+    }
+
+    // The equivalent visual on a new connection to the same server
+    pub(crate) unsafe fn reopen(&self, dpy: *mut Display, root: Window) -> Option<Self> {
+        if self.owns_colormap {
+            Self::with_depth(dpy, root, self.depth)
+        } else {
+            Some(Self::default_for(dpy))
+        }
+    }
+
+    // Anything allocated from the colormap must be freed before calling this
+    pub(crate) unsafe fn free(&self, dpy: *mut Display) {
+        if self.owns_colormap {
+            XFreeColormap(dpy, self.colormap);
+        }
+    }
+}
@@ -0,0 +1,55 @@
+//! Properties of the windows that the drawable is flushed to
+use crate::Draw;
+use std::os::raw::c_ulong;
+use x11::xlib::{
+    False, PropModeReplace, Window, XChangeProperty, XFlush, XInternAtom,
+    XSetWindowBackgroundPixmap, XA_CARDINAL,
+};
+
+impl Draw {
+    /// Set the `_NET_WM_WINDOW_OPACITY` property of the given window, where 0.0 is fully
+    /// transparent and 1.0 is fully opaque. Values outside of this range are clamped.
+    ///
+    /// This is only a hint: it has no visible effect unless a compositor is running.
+    pub fn set_window_opacity(&mut self, win: u32, opacity: f64) {
+        self.assert_thread();
+        let opacity = if opacity.is_nan() {
+            1.0
+        } else {
+            opacity.clamp(0.0, 1.0)
+        };
+        // Xlib expects format 32 property data as an array of longs
+        let val = (opacity * u32::MAX as f64).round() as c_ulong;
+
+        unsafe {
+            let atom = XInternAtom(self.dpy, c"_NET_WM_WINDOW_OPACITY".as_ptr(), False);
+            XChangeProperty(
+                self.dpy,
+                win as Window,
+                atom,
+                XA_CARDINAL,
+                32,
+                PropModeReplace,
+                &val as *const c_ulong as *const _,
+                1,
+            );
+            XFlush(self.dpy);
+        }
+    }
+
+    /// Use the drawable as the background pixmap of the given window so that the X server
+    /// repaints exposed regions of the window from it without needing to handle Expose events.
+    ///
+    /// The server is free to either copy the drawable or keep referencing it, so later drawing
+    /// may not be reflected in the background: call this again after drawing (or after a
+    /// [Draw::resize], which replaces the drawable) to update it. This does not repaint the
+    /// window itself so [Draw::flush_to] is still needed to show new content immediately.
+    pub fn set_as_window_background(&mut self, win: u32) {
+        self.assert_thread();
+
+        unsafe {
+            XSetWindowBackgroundPixmap(self.dpy, win as Window, self.drawable);
+            XFlush(self.dpy);
+        }
+    }
+}
@@ -0,0 +1,102 @@
+//! Trapping X errors so that they can be handled rather than aborting the process
+//!
+//! The default Xlib error handler prints the error and exits. Rather than replacing it for
+//! the whole process, the handler here is only installed for the duration of a trapped call
+//! after which the previously installed handler is restored. This means that applications
+//! are free to install their own handler using XSetErrorHandler: it will remain in effect
+//! for everything outside of the calls made by this crate that are explicitly trapped.
+use std::{
+    os::raw::{c_int, c_ulong},
+    sync::Mutex,
+};
+use x11::xlib::{Display, False, XErrorEvent, XNextRequest, XSetErrorHandler, XSync, XID};
+
+type ErrorHandler = unsafe extern "C" fn(*mut Display, *mut XErrorEvent) -> c_int;
+
+// Xlib error handlers are process global and are not passed any user data so the details of
+// the active trap need to be stashed somewhere the handler can find them.
+static TRAP: Mutex<Option<Trap>> = Mutex::new(None);
+
+#[derive(Debug)]
+struct Trap {
+    // The Display is only compared against so it is stored as an address to keep this Send
+    dpy: usize,
+    first_serial: c_ulong,
+    prev: Option<ErrorHandler>,
+    err: Option<XErrorInfo>,
+}
+
+/// The details of an error event returned by the X server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct XErrorInfo {
+    pub error_code: u8,
+    pub request_code: u8,
+    pub minor_code: u8,
+    pub resource_id: XID,
+}
+
+unsafe extern "C" fn trap_handler(dpy: *mut Display, e: *mut XErrorEvent) -> c_int {
+    let prev = match TRAP.lock() {
+        Ok(mut guard) => match guard.as_mut() {
+            Some(trap) if trap.dpy == dpy as usize && (*e).serial >= trap.first_serial => {
+                // Only the first error is kept as any that follow are likely caused by it
+                let e = &*e;
+                trap.err.get_or_insert(XErrorInfo {
+                    error_code: e.error_code,
+                    request_code: e.request_code,
+                    minor_code: e.minor_code,
+                    resource_id: e.resourceid,
+                });
+
+                return 0;
+            }
+            Some(trap) => trap.prev,
+            None => None,
+        },
+        Err(_) => None,
+    };
+
+    // Errors for other displays or for requests made before the trap was set up belong to
+    // whoever installed the previous handler
+    match prev {
+        Some(handler) => handler(dpy, e),
+        None => 0,
+    }
+}
+
+/// Run `f` with a non-fatal error handler installed, returning the first error (if any)
+/// generated by the requests it makes.
+///
+/// Only errors on `dpy` for requests made from the start of `f` onwards are trapped: the
+/// display is synced after running `f` so that all of them have been received, and any
+/// other errors are passed on to the previously installed handler.
+///
+/// This is not thread safe: Xlib error handlers are process global so `f` must not call
+/// trap_errors itself, and no other thread may install an error handler or trap errors
+/// while it runs.
+pub(crate) unsafe fn trap_errors<T>(
+    dpy: *mut Display,
+    f: impl FnOnce() -> T,
+) -> (T, Option<XErrorInfo>) {
+    let prev = XSetErrorHandler(Some(trap_handler));
+    if let Ok(mut trap) = TRAP.lock() {
+        *trap = Some(Trap {
+            dpy: dpy as usize,
+            first_serial: XNextRequest(dpy),
+            prev,
+            err: None,
+        });
+    }
+
+    let res = f();
+
+    XSync(dpy, False);
+    XSetErrorHandler(prev);
+    let err = TRAP
+        .lock()
+        .ok()
+        .and_then(|mut trap| trap.take())
+        .and_then(|trap| trap.err);
+
+    (res, err)
+}